@@ -0,0 +1,68 @@
+use std::fmt;
+use warp::hyper::StatusCode;
+
+/// Rejections raised by Valence's own request filters, ahead of any handler
+#[derive(Debug, Clone)]
+pub enum ValenceRejection {
+    Unauthorized,
+    StaleRequest,
+    ReplayedRequest,
+    Unavailable,
+    InvalidBody,
+    ReservedAddress,
+    Blocked,
+    NotAddressOwner,
+    RateLimited { retry_after: u64 },
+}
+
+impl ValenceRejection {
+    /// HTTP status to respond with
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ValenceRejection::Unauthorized => StatusCode::UNAUTHORIZED,
+            ValenceRejection::StaleRequest => StatusCode::UNAUTHORIZED,
+            ValenceRejection::ReplayedRequest => StatusCode::UNAUTHORIZED,
+            ValenceRejection::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ValenceRejection::InvalidBody => StatusCode::BAD_REQUEST,
+            ValenceRejection::ReservedAddress => StatusCode::BAD_REQUEST,
+            ValenceRejection::Blocked => StatusCode::FORBIDDEN,
+            ValenceRejection::NotAddressOwner => StatusCode::FORBIDDEN,
+            ValenceRejection::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    /// Seconds the client should wait before retrying, if the rejection is temporary
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            ValenceRejection::RateLimited { retry_after } => Some(*retry_after),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ValenceRejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValenceRejection::Unauthorized => write!(f, "Unauthorized"),
+            ValenceRejection::StaleRequest => write!(
+                f,
+                "Request timestamp is missing or outside of the replay window"
+            ),
+            ValenceRejection::ReplayedRequest => {
+                write!(f, "Request nonce is missing, malformed or already used")
+            }
+            ValenceRejection::Unavailable => write!(f, "Service temporarily unavailable"),
+            ValenceRejection::InvalidBody => write!(f, "Request body is malformed"),
+            ValenceRejection::ReservedAddress => {
+                write!(f, "Address is reserved for the node's own state")
+            }
+            ValenceRejection::Blocked => write!(f, "Sender or address is blocked"),
+            ValenceRejection::NotAddressOwner => {
+                write!(f, "Address is not owned by the request's public key")
+            }
+            ValenceRejection::RateLimited { .. } => write!(f, "Too many requests"),
+        }
+    }
+}
+
+impl warp::reject::Reject for ValenceRejection {}
@@ -1,16 +1,37 @@
-use crate::api::utils::{delete_from_db, retrieve_from_db, serialize_all_entries};
+use crate::api::errors::ValenceRejection;
+use crate::api::utils::{
+    check_quota, check_store, compute_usage, construct_pow_challenge, delete_from_db, read_entries,
+    read_entry, retrieve_from_db, serialize_all_entries, verify_pow_stamp,
+};
+use crate::blocklist::BlockKind;
+use crate::constants::METRICS_CONTENT_TYPE;
 use crate::db::handler::{CacheHandler, KvStoreConnection};
-use crate::interfaces::{SetRequestData, SetSaveData};
-use crate::utils::save_cuckoo_filter_to_disk;
+use crate::db::schema::{encode_record, get_records, set_record};
+use crate::interfaces::{
+    AllowRequest, BlockRequest, BlocklistConnection, ConfigView, FilterCheck, FilterConnection,
+    KeyringConnection, NodeStats, ProofOfWork, QuotaConfig, ReadinessReport, SetDataConfig,
+    SetRequestData, SetSaveData,
+};
+use crate::logging::{redact_headers, redact_payload};
+use crate::metrics::{record_cache_lookup, record_db_fallback, render_metrics, CacheOutcome};
+use crate::utils::{
+    add_to_allowlist, add_to_blocklist, get_allowlist, is_allowed_sender, is_internal_key,
+    rebuild_filter_in_place, remove_from_allowlist, remove_from_blocklist, rotate_filter_secret,
+    save_filter_to_disk,
+};
+use chrono::Utc;
 use futures::lock::Mutex;
 use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, instrument, warn};
 use valence_core::api::errors::ApiErrorType;
-use valence_core::api::interfaces::CFilterConnection;
 use valence_core::api::responses::{json_serialize_embed, CallResponse, JsonReply};
 use valence_core::utils::serialize_data;
+use warp::hyper::header::CONTENT_TYPE;
+use warp::hyper::StatusCode;
+use warp::reply::Response;
+use warp::{Rejection, Reply};
 
 // ========= BASE HANDLERS ========= //
 
@@ -22,7 +43,9 @@ use valence_core::utils::serialize_data;
 /// * `value_id` - Value ID to retrieve (Optional, if not provided, all values for the address are retrieved)
 /// * `db` - Database connection
 /// * `cache` - Cache connection
-/// * `c_filter` - Cuckoo filter connection
+/// * `filter` - Membership filter connection
+/// * `keyring` - Keys to decrypt data with, if encryption at rest is enabled
+#[instrument(level = "debug", skip_all)]
 pub async fn get_data_handler<
     D: KvStoreConnection + Clone + Send + 'static,
     C: KvStoreConnection + Clone + Send + 'static,
@@ -31,32 +54,36 @@ pub async fn get_data_handler<
     value_id: Option<String>,
     db: Arc<Mutex<D>>,
     cache: Arc<Mutex<C>>,
-    c_filter: CFilterConnection,
+    filter: FilterConnection,
+    keyring: KeyringConnection,
 ) -> Result<JsonReply, JsonReply> {
     let r = CallResponse::new("get_data");
-    info!("GET_DATA requested with headers: {:?}", headers);
+    info!(
+        "GET_DATA requested with headers: {:?}",
+        redact_headers(&headers)
+    );
 
     let address = headers
         .get("address")
         .and_then(|n| n.to_str().ok())
         .unwrap_or_default();
 
-    // Check if address is in cuckoo filter
-    if !c_filter.lock().await.contains(&address) {
+    // Check if address is in membership filter
+    if !filter.contains(address).await {
         error!("{}", ApiErrorType::CuckooFilterLookupFailed);
         return r.into_err_internal(ApiErrorType::CuckooFilterLookupFailed);
     }
 
     // Check cache first
-    let mut cache_lock_result = cache.lock().await;
     let cache_result: Result<Option<HashMap<String, String>>, _> =
-        cache_lock_result.get_data::<String>(address, None).await;
+        cache.lock().await.get_data::<String>(address, None).await;
 
     match cache_result {
         Ok(value) => {
             match value {
                 Some(value) => {
-                    info!("Data retrieved from cache: {:?}", value);
+                    record_cache_lookup(CacheOutcome::Hit);
+                    info!("Data retrieved from cache: {}", redact_payload(&value));
 
                     if let Some(id) = value_id {
                         if !value.contains_key(&id) {
@@ -65,33 +92,47 @@ pub async fn get_data_handler<
 
                         let data = value.get(&id).unwrap().clone();
                         let final_result: Value = serde_json::from_str(&data).unwrap();
-                        return r.into_ok(
-                            "Data retrieved successfully",
-                            json_serialize_embed(final_result),
-                        );
+                        return match read_entry(&keyring, address, &id, final_result) {
+                            Ok(final_result) => r.into_ok(
+                                "Data retrieved successfully",
+                                json_serialize_embed(final_result),
+                            ),
+                            Err(e) => {
+                                error!("Failed to decrypt data for address {}: {}", address, e);
+                                r.into_err_internal(ApiErrorType::DataDeserializationFailed)
+                            }
+                        };
                     }
 
-                    let final_value = serialize_all_entries(value);
-
-                    return r.into_ok(
-                        "Data retrieved successfully",
-                        json_serialize_embed(final_value),
-                    );
+                    match read_entries(&keyring, address, serialize_all_entries(value)) {
+                        Ok(final_value) => r.into_ok(
+                            "Data retrieved successfully",
+                            json_serialize_embed(final_value),
+                        ),
+                        Err(e) => {
+                            error!("Failed to decrypt data for address {}: {}", address, e);
+                            r.into_err_internal(ApiErrorType::DataDeserializationFailed)
+                        }
+                    }
                 }
                 None => {
                     // Default to checking from DB if cache is empty
+                    record_cache_lookup(CacheOutcome::Miss);
+                    record_db_fallback();
                     debug!(
                         "Cache lookup failed for address: {}, attempting to retrieve data from DB",
                         address
                     );
-                    retrieve_from_db(db, address, value_id.as_deref()).await
+                    retrieve_from_db(db, address, value_id.as_deref(), &keyring).await
                 }
             }
         }
         Err(_) => {
+            record_cache_lookup(CacheOutcome::Error);
+            record_db_fallback();
             debug!("Attempting to retrieve data from DB");
             // Get data from DB
-            retrieve_from_db(db, address, value_id.as_deref()).await
+            retrieve_from_db(db, address, value_id.as_deref(), &keyring).await
         }
     }
 }
@@ -101,30 +142,133 @@ pub async fn get_data_handler<
 /// ### Arguments
 ///
 /// * `payload` - Request payload
+/// * `sender` - Public key of the sender
 /// * `db` - Database connection
 /// * `cache` - Cache connection
-/// * `c_filter` - Cuckoo filter connection
-/// * `cache_ttl` - Cache TTL
+/// * `filter` - Membership filter connection
+/// * `set_config` - Cache TTL, quota, encryption and proof-of-work settings
+/// * `pow` - Proof-of-work headers of the request
+#[instrument(level = "debug", skip_all)]
 pub async fn set_data_handler<
     D: KvStoreConnection + Clone + Send + 'static,
     C: KvStoreConnection + CacheHandler + Clone + Send + 'static,
 >(
     payload: SetRequestData,
+    sender: String,
     db: Arc<Mutex<D>>,
     cache: Arc<Mutex<C>>,
-    c_filter: CFilterConnection,
-    cache_ttl: usize,
+    filter: FilterConnection,
+    set_config: SetDataConfig,
+    pow: ProofOfWork,
 ) -> Result<JsonReply, JsonReply> {
     let r = CallResponse::new("set_data");
-    info!("SET_DATA requested with payload: {:?}", payload);
+    info!(
+        "SET_DATA requested for address {} with data_id {}: {}",
+        payload.address,
+        payload.data_id,
+        redact_payload(&payload.data)
+    );
+
+    // Node state shares the keyspace, so clients may not write to it
+    if is_internal_key(&payload.address) {
+        return r.into_err_bad_req(ApiErrorType::Generic(
+            ValenceRejection::ReservedAddress.to_string(),
+        ));
+    }
+
+    // Senders not on the recipient's allowlist must stamp their writes
+    let difficulty = set_config.pow_difficulty;
+    if difficulty > 0 {
+        let challenge = construct_pow_challenge(
+            &sender,
+            &payload.address,
+            &pow.timestamp,
+            &pow.nonce,
+            &pow.body,
+        );
+        let stamped = pow
+            .stamp
+            .as_deref()
+            .is_some_and(|stamp| verify_pow_stamp(&challenge, stamp, difficulty));
+
+        if !stamped {
+            match is_allowed_sender(&payload.address, &sender, db.clone()).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    info!(
+                        "Missing or insufficient proof of work from sender: {}",
+                        sender
+                    );
+                    return r.into_err(
+                        StatusCode::FORBIDDEN,
+                        ApiErrorType::Generic(format!(
+                            "Proof-of-work stamp with {} leading zero bits required",
+                            difficulty
+                        )),
+                    );
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    return r.into_err_internal(ApiErrorType::DBQueryFailed);
+                }
+            }
+        }
+    }
+
+    // Encrypt before the data reaches either store
+    let data = match &set_config.keyring {
+        Some(keyring) => match keyring.encrypt(&payload.data, &payload.address, &payload.data_id) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("Failed to encrypt data: {}", e);
+                return r.into_err_internal(ApiErrorType::DataSerializationFailed);
+            }
+        },
+        None => payload.data.clone(),
+    };
 
     let data_to_save: SetSaveData = {
         SetSaveData {
             address: payload.address.clone(),
-            data: payload.data.clone(),
+            data,
+            sender: Some(sender.clone()),
+            saved_at: Utc::now().timestamp().max(0) as u64,
         }
     };
 
+    // Check quota and add to DB under one lock, so concurrent sets cannot overshoot it
+    {
+        let mut db_lock = db.lock().await;
+        let mut entries: HashMap<String, SetSaveData> =
+            match get_records(&mut *db_lock, &payload.address, None).await {
+                Ok(entries) => entries.unwrap_or_default(),
+                Err(_) => return r.into_err_internal(ApiErrorType::DBQueryFailed),
+            };
+        entries.insert(payload.data_id.clone(), data_to_save.clone());
+
+        let usage = compute_usage(&entries, set_config.quota);
+        if let Err(e) = check_quota(&usage, &sender) {
+            error!("{} for address: {}", e, payload.address);
+            return r.into_err_with_data(
+                StatusCode::INSUFFICIENT_STORAGE,
+                ApiErrorType::Generic(e),
+                json_serialize_embed(usage),
+            );
+        }
+
+        if set_record(
+            &mut *db_lock,
+            &payload.address,
+            &payload.data_id,
+            &data_to_save,
+        )
+        .await
+        .is_err()
+        {
+            return r.into_err_internal(ApiErrorType::DBInsertionFailed);
+        }
+    }
+
     // Add to cache
     let cache_result = cache
         .lock()
@@ -132,47 +276,31 @@ pub async fn set_data_handler<
         .set_data(
             &payload.address.clone(),
             &payload.data_id,
-            serialize_data(&data_to_save),
+            serialize_data(&encode_record(&data_to_save)),
         )
         .await;
 
-    // Add to DB
-    let db_result = match cache_result {
-        Ok(_) => {
-            // Set key expiry
-            let _ = cache
-                .lock()
-                .await
-                .expire_entry(&payload.address, cache_ttl)
-                .await
-                .map_err(|err| {
-                    error!("Failed to expire cache entry: {:?}", err);
-                });
-
-            db.lock()
-                .await
-                .set_data(&payload.address, &payload.data_id, data_to_save)
-                .await
-        }
-        Err(_) => {
-            return r.into_err_internal(ApiErrorType::CacheInsertionFailed);
-        }
-    };
+    // The DB holds the entry, so requests are served from it while the cache is unavailable
+    if let Err(err) = cache_result {
+        warn!("Failed to cache entry, serving it from the DB: {:?}", err);
+    }
 
-    // Add to cuckoo filter
-    let c_filter_result = match db_result {
-        Ok(_) => c_filter.lock().await.add(&payload.address),
-        Err(_) => {
-            return r.into_err_internal(ApiErrorType::DBInsertionFailed);
-        }
-    };
+    // Set key expiry
+    let _ = cache
+        .lock()
+        .await
+        .expire_entry(&payload.address, set_config.cache_ttl)
+        .await
+        .map_err(|err| {
+            error!("Failed to expire cache entry: {:?}", err);
+        });
 
-    match c_filter_result {
+    // Add to membership filter
+    match filter.add(&payload.address).await {
         Ok(_) => {
-            // Save latest result to disk
-            let cf_lock = c_filter.lock().await;
-            if let Err(err) = save_cuckoo_filter_to_disk(&cf_lock, db).await {
-                error!("Failed to save cuckoo filter to disk: {:?}", err);
+            // Save changed shards to disk
+            if let Err(err) = save_filter_to_disk(&filter, db).await {
+                error!("Failed to save filter to disk: {:?}", err);
             }
 
             // Return success
@@ -193,7 +321,8 @@ pub async fn set_data_handler<
 /// * `value_id` - Value ID to retrieve (Optional, if not provided, all values for the address are deleted)
 /// * `db` - Database connection
 /// * `cache` - Cache connection
-/// * `c_filter` - Cuckoo filter connection
+/// * `filter` - Membership filter connection
+#[instrument(level = "debug", skip_all)]
 pub async fn del_data_handler<
     D: KvStoreConnection + Clone + Send + 'static,
     C: KvStoreConnection + Clone + Send + 'static,
@@ -202,36 +331,492 @@ pub async fn del_data_handler<
     value_id: Option<String>,
     db: Arc<Mutex<D>>,
     cache: Arc<Mutex<C>>,
-    c_filter: CFilterConnection,
+    filter: FilterConnection,
 ) -> Result<JsonReply, JsonReply> {
     let r = CallResponse::new("del_data");
-    info!("DEL_DATA requested with headers: {:?}", headers);
+    info!(
+        "DEL_DATA requested with headers: {:?}",
+        redact_headers(&headers)
+    );
 
     let address = headers
         .get("address")
         .and_then(|n| n.to_str().ok())
         .unwrap_or_default();
 
-    // delete address in cuckoo filter if no value_id is provided
-    if value_id.is_none() && !c_filter.lock().await.delete(&address) {
-        error!("Address not found in cuckoo filter");
+    // delete address in membership filter if no value_id is provided
+    if value_id.is_none() && !filter.delete(address).await {
+        error!("Address not found in membership filter");
         return r.into_err_internal(ApiErrorType::CuckooFilterLookupFailed);
     }
 
     // Check cache
-    let mut cache_lock_result = cache.lock().await;
-    let cache_result = cache_lock_result
+    let cache_result = cache
+        .lock()
+        .await
         .del_data(address, value_id.as_deref())
         .await;
 
+    // Copies the cache could not drop are dropped once it is available again
     match cache_result {
-        Ok(_) => {
-            debug!("Data deleted from cache");
-            return delete_from_db(db, address, value_id.as_deref()).await;
+        Ok(_) => debug!("Data deleted from cache"),
+        Err(_) => warn!("Cache deletion failed for address: {}", address),
+    }
+    delete_from_db(db, address, value_id.as_deref()).await
+}
+
+/// Route to get the storage used by an address and its quota
+///
+/// ### Arguments
+///
+/// * `headers` - Request headers
+/// * `db` - Database connection
+/// * `quota` - Storage quota per address
+#[instrument(level = "debug", skip_all)]
+pub async fn usage_handler<D: KvStoreConnection + Clone + Send + 'static>(
+    headers: warp::hyper::HeaderMap,
+    db: Arc<Mutex<D>>,
+    quota: QuotaConfig,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new("usage");
+
+    let address = headers
+        .get("address")
+        .and_then(|n| n.to_str().ok())
+        .unwrap_or_default();
+    info!("USAGE requested for address: {}", address);
+
+    let entries: HashMap<String, SetSaveData> =
+        match get_records(&mut *db.lock().await, address, None).await {
+            Ok(entries) => entries.unwrap_or_default(),
+            Err(_) => return r.into_err_internal(ApiErrorType::DBQueryFailed),
+        };
+
+    r.into_ok(
+        "Usage retrieved successfully",
+        json_serialize_embed(compute_usage(&entries, quota)),
+    )
+}
+
+/// Route to list the senders on an address's allowlist
+///
+/// ### Arguments
+///
+/// * `headers` - Request headers
+/// * `db` - Database connection
+#[instrument(level = "debug", skip_all)]
+pub async fn get_allowlist_handler<D: KvStoreConnection + Clone + Send + 'static>(
+    headers: warp::hyper::HeaderMap,
+    db: Arc<Mutex<D>>,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new("get_allowlist");
+
+    let address = headers
+        .get("address")
+        .and_then(|n| n.to_str().ok())
+        .unwrap_or_default();
+    info!("GET_ALLOWLIST requested for address: {}", address);
+
+    match get_allowlist(address, db).await {
+        Ok(entries) => r.into_ok(
+            "Allowlist retrieved successfully",
+            json_serialize_embed(entries),
+        ),
+        Err(e) => {
+            error!("{}", e);
+            r.into_err_internal(ApiErrorType::DBQueryFailed)
         }
-        Err(_) => {
-            error!("Cache deletion failed for address: {}", address);
-            return r.into_err_internal(ApiErrorType::CacheDeleteFailed);
+    }
+}
+
+/// Route to add a sender to an address's allowlist
+///
+/// ### Arguments
+///
+/// * `headers` - Request headers
+/// * `request` - Sender to allow
+/// * `db` - Database connection
+#[instrument(level = "debug", skip_all)]
+pub async fn allow_sender_handler<D: KvStoreConnection + Clone + Send + 'static>(
+    headers: warp::hyper::HeaderMap,
+    request: AllowRequest,
+    db: Arc<Mutex<D>>,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new("allow_sender");
+
+    let address = headers
+        .get("address")
+        .and_then(|n| n.to_str().ok())
+        .unwrap_or_default();
+    info!(
+        "ALLOW_SENDER requested for address {}: {}",
+        address, request.sender
+    );
+
+    if request.sender.is_empty() {
+        return r.into_err_bad_req(ApiErrorType::Generic(
+            "Allowed sender cannot be empty".to_string(),
+        ));
+    }
+
+    match add_to_allowlist(address, &request.sender, db).await {
+        Ok(entry) => r.into_ok("Sender allowed successfully", json_serialize_embed(entry)),
+        Err(e) => {
+            error!("{}", e);
+            r.into_err_internal(ApiErrorType::DBInsertionFailed)
+        }
+    }
+}
+
+/// Route to remove a sender from an address's allowlist
+///
+/// ### Arguments
+///
+/// * `headers` - Request headers
+/// * `sender` - Public key of the sender
+/// * `db` - Database connection
+#[instrument(level = "debug", skip_all)]
+pub async fn disallow_sender_handler<D: KvStoreConnection + Clone + Send + 'static>(
+    headers: warp::hyper::HeaderMap,
+    sender: String,
+    db: Arc<Mutex<D>>,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new("disallow_sender");
+
+    let address = headers
+        .get("address")
+        .and_then(|n| n.to_str().ok())
+        .unwrap_or_default();
+    info!(
+        "DISALLOW_SENDER requested for address {}: {}",
+        address, sender
+    );
+
+    match remove_from_allowlist(address, &sender, db).await {
+        Ok(true) => r.into_ok(
+            "Sender disallowed successfully",
+            json_serialize_embed(sender),
+        ),
+        Ok(false) => r.into_err(StatusCode::NOT_FOUND, ApiErrorType::DataNotFound),
+        Err(e) => {
+            error!("{}", e);
+            r.into_err_internal(ApiErrorType::ValueDeleteFailed)
+        }
+    }
+}
+
+// ========= ADMIN HANDLERS ========= //
+
+/// Route to get filter and storage statistics
+///
+/// ### Arguments
+///
+/// * `db` - Database connection
+/// * `cache` - Cache connection
+/// * `filter` - Membership filter connection
+#[instrument(level = "debug", skip_all)]
+pub async fn stats_handler<
+    D: KvStoreConnection + Clone + Send + 'static,
+    C: KvStoreConnection + Clone + Send + 'static,
+>(
+    db: Arc<Mutex<D>>,
+    cache: Arc<Mutex<C>>,
+    filter: FilterConnection,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new("stats");
+    info!("STATS requested");
+
+    let filter_stats = filter.stats().await;
+
+    let db_stats = match db.lock().await.get_stats().await {
+        Ok(stats) => stats,
+        Err(e) => {
+            error!("Failed to get DB stats: {:?}", e);
+            return r.into_err_internal(ApiErrorType::DBQueryFailed);
+        }
+    };
+
+    let cache_stats = match cache.lock().await.get_stats().await {
+        Ok(stats) => stats,
+        Err(e) => {
+            error!("Failed to get cache stats: {:?}", e);
+            return r.into_err_internal(ApiErrorType::CacheQueryFailed);
+        }
+    };
+
+    r.into_ok(
+        "Stats retrieved successfully",
+        json_serialize_embed(NodeStats {
+            filter: filter_stats,
+            db: db_stats,
+            cache: cache_stats,
+        }),
+    )
+}
+
+/// Route to purge all data held for an address from the cache, DB and filter
+///
+/// ### Arguments
+///
+/// * `address` - Address to purge
+/// * `db` - Database connection
+/// * `cache` - Cache connection
+/// * `filter` - Membership filter connection
+#[instrument(level = "debug", skip_all)]
+pub async fn purge_address_handler<
+    D: KvStoreConnection + Clone + Send + 'static,
+    C: KvStoreConnection + Clone + Send + 'static,
+>(
+    address: String,
+    db: Arc<Mutex<D>>,
+    cache: Arc<Mutex<C>>,
+    filter: FilterConnection,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new("purge_address");
+    info!("PURGE_ADDRESS requested for address: {}", address);
+
+    if is_internal_key(&address) {
+        return r.into_err_bad_req(ApiErrorType::Generic(
+            "Cannot purge internal node state".to_string(),
+        ));
+    }
+
+    if let Err(e) = cache.lock().await.del_data(&address, None).await {
+        error!("Failed to purge address from cache: {:?}", e);
+        return r.into_err_internal(ApiErrorType::CacheDeleteFailed);
+    }
+
+    if let Err(e) = db.lock().await.del_data(&address, None).await {
+        error!("Failed to purge address from DB: {:?}", e);
+        return r.into_err_internal(ApiErrorType::ValueDeleteFailed);
+    }
+
+    if filter.delete(&address).await {
+        if let Err(err) = save_filter_to_disk(&filter, db).await {
+            error!("Failed to save filter to disk: {:?}", err);
+        }
+    }
+
+    r.into_ok("Address purged successfully", json_serialize_embed(address))
+}
+
+/// Route to rebuild the membership filter from the addresses in the DB, optionally
+/// under a new filter secret
+///
+/// ### Arguments
+///
+/// * `db` - Database connection
+/// * `filter` - Membership filter connection
+/// * `rotate` - Whether to generate and persist a new filter secret
+/// * `secret_configured` - Whether the filter secret is set in config, which prevents rotation
+#[instrument(level = "debug", skip_all)]
+pub async fn rebuild_filter_handler<D: KvStoreConnection + Clone + Send + 'static>(
+    db: Arc<Mutex<D>>,
+    filter: FilterConnection,
+    rotate: bool,
+    secret_configured: bool,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new("rebuild_filter");
+    info!("REBUILD_FILTER requested (rotate: {})", rotate);
+
+    if rotate && secret_configured {
+        return r.into_err_bad_req(ApiErrorType::Generic(
+            "Filter secret is set in config and cannot be rotated".to_string(),
+        ));
+    }
+
+    let result = if rotate {
+        rotate_filter_secret(&filter, db).await
+    } else {
+        rebuild_filter_in_place(&filter, db).await
+    };
+
+    if let Err(e) = result {
+        error!("Failed to rebuild filter: {}", e);
+        return r.into_err_internal(ApiErrorType::Generic(e));
+    }
+
+    r.into_ok(
+        "Filter rebuilt successfully",
+        json_serialize_embed(filter.stats().await),
+    )
+}
+
+/// Route to inspect the node's config, without secrets
+///
+/// ### Arguments
+///
+/// * `config` - View of the loaded config
+#[instrument(level = "debug", skip_all)]
+pub async fn inspect_config_handler(config: ConfigView) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new("config");
+    info!("CONFIG requested");
+
+    r.into_ok(
+        "Config retrieved successfully",
+        json_serialize_embed(config),
+    )
+}
+
+/// Route to list the operator's blocklist
+///
+/// ### Arguments
+///
+/// * `blocklist` - Blocked addresses and public keys
+#[instrument(level = "debug", skip_all)]
+pub async fn list_blocklist_handler(
+    blocklist: BlocklistConnection,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new("blocklist");
+    info!("BLOCKLIST requested");
+
+    r.into_ok(
+        "Blocklist retrieved successfully",
+        json_serialize_embed(blocklist.entries()),
+    )
+}
+
+/// Route to block an address or public key
+///
+/// ### Arguments
+///
+/// * `request` - What to block
+/// * `db` - Database connection
+/// * `blocklist` - Blocked addresses and public keys
+#[instrument(level = "debug", skip_all)]
+pub async fn block_handler<D: KvStoreConnection + Clone + Send + 'static>(
+    request: BlockRequest,
+    db: Arc<Mutex<D>>,
+    blocklist: BlocklistConnection,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new("block");
+    info!("BLOCK requested for {}: {}", request.kind, request.value);
+
+    if request.value.is_empty() {
+        return r.into_err_bad_req(ApiErrorType::Generic(
+            "Blocked value cannot be empty".to_string(),
+        ));
+    }
+
+    match add_to_blocklist(request, &blocklist, db).await {
+        Ok(entry) => r.into_ok("Blocked successfully", json_serialize_embed(entry)),
+        Err(e) => {
+            error!("{}", e);
+            r.into_err_internal(ApiErrorType::DBInsertionFailed)
+        }
+    }
+}
+
+/// Route to unblock an address or public key
+///
+/// ### Arguments
+///
+/// * `kind` - What the value is
+/// * `value` - Address or public key to unblock
+/// * `db` - Database connection
+/// * `blocklist` - Blocked addresses and public keys
+#[instrument(level = "debug", skip_all)]
+pub async fn unblock_handler<D: KvStoreConnection + Clone + Send + 'static>(
+    kind: BlockKind,
+    value: String,
+    db: Arc<Mutex<D>>,
+    blocklist: BlocklistConnection,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new("unblock");
+    info!("UNBLOCK requested for {}: {}", kind, value);
+
+    match remove_from_blocklist(kind, &value, &blocklist, db).await {
+        Ok(true) => r.into_ok("Unblocked successfully", json_serialize_embed(value)),
+        Ok(false) => r.into_err(StatusCode::NOT_FOUND, ApiErrorType::DataNotFound),
+        Err(e) => {
+            error!("{}", e);
+            r.into_err_internal(ApiErrorType::ValueDeleteFailed)
+        }
+    }
+}
+
+// ========= HEALTH HANDLERS ========= //
+
+/// Route to check that the node is alive
+#[instrument(level = "debug", skip_all)]
+pub async fn healthz_handler() -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new("healthz");
+
+    r.into_ok("Node is alive", json_serialize_embed(true))
+}
+
+/// Route to check that the node can serve requests, pinging both stores and
+/// checking that the membership filter is loaded. The node stays ready without
+/// the cache, but is reported as degraded
+///
+/// ### Arguments
+///
+/// * `db` - Database connection
+/// * `cache` - Cache connection
+/// * `filter` - Membership filter connection
+#[instrument(level = "debug", skip_all)]
+pub async fn readyz_handler<
+    D: KvStoreConnection + Clone + Send + 'static,
+    C: KvStoreConnection + Clone + Send + 'static,
+>(
+    db: Arc<Mutex<D>>,
+    cache: Arc<Mutex<C>>,
+    filter: FilterConnection,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new("readyz");
+
+    let (db, cache) = futures::join!(check_store(db), check_store(cache));
+    let filter = FilterCheck {
+        ready: true,
+        items: filter.len().await,
+    };
+
+    let report = ReadinessReport {
+        ready: db.ready && filter.ready,
+        degraded: !cache.ready,
+        db,
+        cache,
+        filter,
+    };
+
+    if !report.ready {
+        error!("Node not ready: {:?}", report);
+        return r.into_err_with_data(
+            StatusCode::SERVICE_UNAVAILABLE,
+            ApiErrorType::Generic("Node is not ready".to_string()),
+            json_serialize_embed(report),
+        );
+    }
+
+    if report.degraded {
+        warn!("Node degraded: {:?}", report);
+        return r.into_ok(
+            "Node is ready without the cache",
+            json_serialize_embed(report),
+        );
+    }
+
+    r.into_ok("Node is ready", json_serialize_embed(report))
+}
+
+// ========= METRICS HANDLERS ========= //
+
+/// Route to export the node's metrics in the Prometheus text format
+///
+/// ### Arguments
+///
+/// * `filter` - Membership filter connection
+#[instrument(level = "debug", skip_all)]
+pub async fn metrics_handler(filter: FilterConnection) -> Result<Response, Rejection> {
+    debug!("METRICS requested");
+
+    match render_metrics(&filter).await {
+        Ok(body) => {
+            Ok(warp::reply::with_header(body, CONTENT_TYPE, METRICS_CONTENT_TYPE).into_response())
+        }
+        Err(e) => {
+            error!("{}", e);
+            Ok(warp::reply::with_status(e, StatusCode::INTERNAL_SERVER_ERROR).into_response())
         }
     }
 }
@@ -1,12 +1,29 @@
-use crate::api::handlers::{del_data_handler, get_data_handler, set_data_handler};
+use crate::api::errors::ValenceRejection;
+use crate::api::handlers::{
+    allow_sender_handler, block_handler, del_data_handler, disallow_sender_handler,
+    get_allowlist_handler, get_data_handler, healthz_handler, inspect_config_handler,
+    list_blocklist_handler, metrics_handler, purge_address_handler, readyz_handler,
+    rebuild_filter_handler, set_data_handler, stats_handler, unblock_handler, usage_handler,
+};
+use crate::api::utils::{
+    address_owner_middleware, admin_auth_middleware, ip_rate_limit_middleware,
+    sender_rate_limit_middleware, signed_body_middleware, signed_request_middleware,
+};
+use crate::blocklist::BlockKind;
+use crate::constants::ADMIN_ACTOR;
 use crate::db::handler::{CacheHandler, KvStoreConnection};
+use crate::interfaces::{
+    AllowRequest, BlockRequest, BlocklistConnection, ConfigView, FilterConnection,
+    KeyringConnection, ProofOfWork, QuotaConfig, RebuildQuery, RouteRateLimits, SetDataConfig,
+    SetRequestData, SignatureConfig,
+};
+use crate::logging::{audited, AuditAction, AuditEvent};
 use futures::lock::Mutex;
+use percent_encoding::percent_decode_str;
 use std::sync::Arc;
 use tracing::debug;
-use valence_core::api::interfaces::CFilterConnection;
-use valence_core::api::utils::{
-    get_cors, map_api_res, post_cors, sig_verify_middleware, with_node_component,
-};
+use valence_core::api::utils::{get_cors, map_api_res, post_cors, with_node_component};
+use warp::hyper::body::Bytes;
 use warp::{Filter, Rejection, Reply};
 
 // ========== BASE ROUTES ========== //
@@ -19,54 +36,119 @@ use warp::{Filter, Rejection, Reply};
 ///
 /// * `db` - The database connection to use
 /// * `cache` - The cache connection to use
-/// * `cuckoo_filter` - The cuckoo filter connection to use
+/// * `filter` - The membership filter connection to use
+/// * `sig_config` - Request signing settings
+/// * `rate_limits` - Rate limits for the route
+/// * `keyring` - Keys to decrypt data with, if encryption at rest is enabled
+/// * `blocklist` - Blocked addresses and public keys
 pub fn get_data_with_id<
     D: KvStoreConnection + Clone + Send + 'static,
-    C: KvStoreConnection + Clone + Send + 'static,
+    C: KvStoreConnection + CacheHandler + Clone + Send + 'static,
 >(
     db: Arc<Mutex<D>>,
     cache: Arc<Mutex<C>>,
-    cuckoo_filter: CFilterConnection,
+    filter: FilterConnection,
+    sig_config: SignatureConfig,
+    rate_limits: RouteRateLimits,
+    keyring: KeyringConnection,
+    blocklist: BlocklistConnection,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     debug!("Setting up get_data_with_id route");
 
     warp::path("get_data")
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
         .and(warp::get())
-        .and(sig_verify_middleware())
+        .and(ip_rate_limit_middleware(
+            "get_data",
+            cache.clone(),
+            rate_limits.ip,
+        ))
+        .and(signed_request_middleware(
+            cache.clone(),
+            sig_config,
+            blocklist,
+        ))
+        .and(sender_rate_limit_middleware(
+            "get_data",
+            cache.clone(),
+            rate_limits.sender,
+        ))
         .and(warp::header::headers_cloned())
-        .and(warp::path::param::<String>())
         .and(with_node_component(cache))
         .and(with_node_component(db))
-        .and(with_node_component(cuckoo_filter))
-        .and_then(move |_, headers, value_id: String, cache, db, cf| {
+        .and(with_node_component(filter))
+        .and(with_node_component(keyring))
+        .and_then(move |value_id: String, _, headers, cache, db, cf, kr| {
             // Add type annotation for headers parameter
             debug!("GET_DATA requested with value_id({:?})", value_id);
-            map_api_res(get_data_handler(headers, Some(value_id), db, cache, cf))
+            let event = AuditEvent::from_headers(AuditAction::Read, &headers, Some(&value_id));
+            map_api_res(audited(
+                event,
+                get_data_handler(headers, Some(value_id), db, cache, cf, kr),
+            ))
         })
         .with(get_cors())
 }
 
+/// GET /get_data
+///
+/// Retrieves all data associated with a given address
+///
+/// ### Arguments
+///
+/// * `db` - The database connection to use
+/// * `cache` - The cache connection to use
+/// * `filter` - The membership filter connection to use
+/// * `sig_config` - Request signing settings
+/// * `rate_limits` - Rate limits for the route
+/// * `keyring` - Keys to decrypt data with, if encryption at rest is enabled
+/// * `blocklist` - Blocked addresses and public keys
 pub fn get_data<
     D: KvStoreConnection + Clone + Send + 'static,
-    C: KvStoreConnection + Clone + Send + 'static,
+    C: KvStoreConnection + CacheHandler + Clone + Send + 'static,
 >(
     db: Arc<Mutex<D>>,
     cache: Arc<Mutex<C>>,
-    cuckoo_filter: CFilterConnection,
+    filter: FilterConnection,
+    sig_config: SignatureConfig,
+    rate_limits: RouteRateLimits,
+    keyring: KeyringConnection,
+    blocklist: BlocklistConnection,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     debug!("Setting up get_data route");
 
     warp::path("get_data")
+        .and(warp::path::end())
         .and(warp::get())
-        .and(sig_verify_middleware())
+        .and(ip_rate_limit_middleware(
+            "get_data",
+            cache.clone(),
+            rate_limits.ip,
+        ))
+        .and(signed_request_middleware(
+            cache.clone(),
+            sig_config,
+            blocklist,
+        ))
+        .and(sender_rate_limit_middleware(
+            "get_data",
+            cache.clone(),
+            rate_limits.sender,
+        ))
         .and(warp::header::headers_cloned())
         .and(with_node_component(cache))
         .and(with_node_component(db))
-        .and(with_node_component(cuckoo_filter))
-        .and_then(move |_, headers, cache, db, cf| {
+        .and(with_node_component(filter))
+        .and(with_node_component(keyring))
+        .and_then(move |_, headers, cache, db, cf, kr| {
             // Add type annotation for headers parameter
             debug!("GET_DATA requested");
-            map_api_res(get_data_handler(headers, None, db, cache, cf))
+            let event = AuditEvent::from_headers(AuditAction::Read, &headers, None);
+            map_api_res(audited(
+                event,
+                get_data_handler(headers, None, db, cache, cf, kr),
+            ))
         })
         .with(get_cors())
 }
@@ -79,33 +161,88 @@ pub fn get_data<
 ///
 /// * `db` - The database connection to use
 /// * `cache` - The cache connection to use
-/// * `cuckoo_filter` - The cuckoo filter connection to use
-/// * `body_limit` - The maximum size of the request body
+/// * `filter` - The membership filter connection to use
+/// * `set_config` - Request body limit, cache TTL, quota, encryption and proof-of-work settings
+/// * `sig_config` - Request signing settings
+/// * `rate_limits` - Rate limits for the route
+/// * `blocklist` - Blocked addresses and public keys
 pub fn set_data<
     D: KvStoreConnection + Clone + Send + Sync + 'static,
     C: KvStoreConnection + CacheHandler + Clone + Send + Sync + 'static,
 >(
     db: Arc<Mutex<D>>,
     cache: Arc<Mutex<C>>,
-    cuckoo_filter: CFilterConnection,
-    body_limit: u64,
-    cache_ttl: usize,
+    filter: FilterConnection,
+    set_config: SetDataConfig,
+    sig_config: SignatureConfig,
+    rate_limits: RouteRateLimits,
+    blocklist: BlocklistConnection,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     debug!("Setting up set_data route");
 
     warp::path("set_data")
+        .and(warp::path::end())
         .and(warp::post())
-        .and(sig_verify_middleware())
-        .and(warp::body::content_length_limit(body_limit))
-        .and(warp::body::json())
+        .and(ip_rate_limit_middleware(
+            "set_data",
+            cache.clone(),
+            rate_limits.ip,
+        ))
+        .and(signed_body_middleware(
+            cache.clone(),
+            sig_config,
+            blocklist,
+            set_config.body_limit,
+        ))
+        .and(sender_rate_limit_middleware(
+            "set_data",
+            cache.clone(),
+            rate_limits.sender,
+        ))
+        .and_then(|body: Bytes| async move {
+            serde_json::from_slice::<SetRequestData>(&body)
+                .map(|info| (info, body))
+                .map_err(|_| warp::reject::custom(ValenceRejection::InvalidBody))
+        })
+        .untuple_one()
+        .and(warp::header::<String>("public_key"))
+        .and(warp::header::<String>("timestamp"))
+        .and(warp::header::<String>("nonce"))
+        .and(warp::header::optional::<String>("pow_stamp"))
         .and(with_node_component(cache))
         .and(with_node_component(db))
-        .and(with_node_component(cuckoo_filter))
-        .and(with_node_component(cache_ttl))
-        .and_then(move |_, info, cache, db, cf, cttl| {
-            debug!("SET_DATA requested");
-            map_api_res(set_data_handler(info, db, cache, cf, cttl))
-        })
+        .and(with_node_component(filter))
+        .and(with_node_component(set_config))
+        .and_then(
+            move |info: SetRequestData,
+                  body,
+                  sender: String,
+                  timestamp,
+                  nonce,
+                  stamp,
+                  cache,
+                  db,
+                  cf,
+                  sc| {
+                let pow = ProofOfWork {
+                    timestamp,
+                    nonce,
+                    body,
+                    stamp,
+                };
+                debug!("SET_DATA requested");
+                let event = AuditEvent {
+                    action: AuditAction::Write,
+                    actor: sender.clone(),
+                    address: info.address.clone(),
+                    data_id: Some(info.data_id.clone()),
+                };
+                map_api_res(audited(
+                    event,
+                    set_data_handler(info, sender, db, cache, cf, sc, pow),
+                ))
+            },
+        )
         .with(post_cors())
 }
 
@@ -117,28 +254,585 @@ pub fn set_data<
 ///
 /// * `db` - The database connection to use
 /// * `cache` - The cache connection to use
-/// * `cuckoo_filter` - The cuckoo filter connection to use
+/// * `filter` - The membership filter connection to use
+/// * `sig_config` - Request signing settings
+/// * `rate_limits` - Rate limits for the route
+/// * `blocklist` - Blocked addresses and public keys
 pub fn del_data<
     D: KvStoreConnection + Clone + Send + 'static,
-    C: KvStoreConnection + Clone + Send + 'static,
+    C: KvStoreConnection + CacheHandler + Clone + Send + 'static,
 >(
     db: Arc<Mutex<D>>,
     cache: Arc<Mutex<C>>,
-    cuckoo_filter: CFilterConnection,
+    filter: FilterConnection,
+    sig_config: SignatureConfig,
+    rate_limits: RouteRateLimits,
+    blocklist: BlocklistConnection,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     debug!("Setting up del_data route");
 
     warp::path("del_data")
+        .and(warp::path::end())
         .and(warp::delete())
-        .and(sig_verify_middleware())
+        .and(ip_rate_limit_middleware(
+            "del_data",
+            cache.clone(),
+            rate_limits.ip,
+        ))
+        .and(signed_request_middleware(
+            cache.clone(),
+            sig_config,
+            blocklist,
+        ))
+        .and(sender_rate_limit_middleware(
+            "del_data",
+            cache.clone(),
+            rate_limits.sender,
+        ))
         .and(warp::header::headers_cloned())
         .and(with_node_component(cache))
         .and(with_node_component(db))
-        .and(with_node_component(cuckoo_filter))
+        .and(with_node_component(filter))
         .and_then(move |_, headers, cache, db, cf| {
             // Add type annotation for headers parameter
             debug!("DEL_DATA requested");
-            map_api_res(del_data_handler(headers, None, db, cache, cf))
+            let event = AuditEvent::from_headers(AuditAction::Delete, &headers, None);
+            map_api_res(audited(
+                event,
+                del_data_handler(headers, None, db, cache, cf),
+            ))
+        })
+        .with(get_cors())
+}
+
+/// GET /usage
+///
+/// Retrieves the storage used by a given address and its quota. The request's public key
+/// must own the address
+///
+/// ### Arguments
+///
+/// * `db` - The database connection to use
+/// * `cache` - The cache connection to use
+/// * `sig_config` - Request signing settings
+/// * `rate_limits` - Rate limits for the route
+/// * `quota` - Storage quota per address
+/// * `blocklist` - Blocked addresses and public keys
+pub fn usage<
+    D: KvStoreConnection + Clone + Send + 'static,
+    C: KvStoreConnection + CacheHandler + Clone + Send + 'static,
+>(
+    db: Arc<Mutex<D>>,
+    cache: Arc<Mutex<C>>,
+    sig_config: SignatureConfig,
+    rate_limits: RouteRateLimits,
+    quota: QuotaConfig,
+    blocklist: BlocklistConnection,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    debug!("Setting up usage route");
+
+    warp::path("usage")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(ip_rate_limit_middleware(
+            "usage",
+            cache.clone(),
+            rate_limits.ip,
+        ))
+        .and(signed_request_middleware(
+            cache.clone(),
+            sig_config,
+            blocklist,
+        ))
+        .and(sender_rate_limit_middleware(
+            "usage",
+            cache.clone(),
+            rate_limits.sender,
+        ))
+        .and(address_owner_middleware())
+        .and(warp::header::headers_cloned())
+        .and(with_node_component(db))
+        .and(with_node_component(quota))
+        .and_then(move |_, headers, db, quota| {
+            debug!("USAGE requested");
+            map_api_res(usage_handler(headers, db, quota))
+        })
+        .with(get_cors())
+}
+
+/// GET /allowlist
+///
+/// Retrieves the senders on an address's allowlist, who are exempt from proof of work.
+/// The request's public key must own the address
+///
+/// ### Arguments
+///
+/// * `db` - The database connection to use
+/// * `cache` - The cache connection to use
+/// * `sig_config` - Request signing settings
+/// * `rate_limits` - Rate limits for the route
+/// * `blocklist` - Blocked addresses and public keys
+pub fn get_allowlist<
+    D: KvStoreConnection + Clone + Send + 'static,
+    C: KvStoreConnection + CacheHandler + Clone + Send + 'static,
+>(
+    db: Arc<Mutex<D>>,
+    cache: Arc<Mutex<C>>,
+    sig_config: SignatureConfig,
+    rate_limits: RouteRateLimits,
+    blocklist: BlocklistConnection,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    debug!("Setting up get_allowlist route");
+
+    warp::path("allowlist")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(ip_rate_limit_middleware(
+            "allowlist",
+            cache.clone(),
+            rate_limits.ip,
+        ))
+        .and(signed_request_middleware(
+            cache.clone(),
+            sig_config,
+            blocklist,
+        ))
+        .and(sender_rate_limit_middleware(
+            "allowlist",
+            cache.clone(),
+            rate_limits.sender,
+        ))
+        .and(address_owner_middleware())
+        .and(warp::header::headers_cloned())
+        .and(with_node_component(db))
+        .and_then(move |_, headers, db| {
+            debug!("GET_ALLOWLIST requested");
+            map_api_res(get_allowlist_handler(headers, db))
         })
         .with(get_cors())
 }
+
+/// POST /allowlist
+///
+/// Adds a sender to an address's allowlist, exempting them from proof of work.
+/// The request's public key must own the address
+///
+/// ### Arguments
+///
+/// * `db` - The database connection to use
+/// * `cache` - The cache connection to use
+/// * `sig_config` - Request signing settings
+/// * `rate_limits` - Rate limits for the route
+/// * `blocklist` - Blocked addresses and public keys
+/// * `body_limit` - The maximum size of the request body
+pub fn allow_sender<
+    D: KvStoreConnection + Clone + Send + 'static,
+    C: KvStoreConnection + CacheHandler + Clone + Send + 'static,
+>(
+    db: Arc<Mutex<D>>,
+    cache: Arc<Mutex<C>>,
+    sig_config: SignatureConfig,
+    rate_limits: RouteRateLimits,
+    blocklist: BlocklistConnection,
+    body_limit: u64,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    debug!("Setting up allow_sender route");
+
+    warp::path("allowlist")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(ip_rate_limit_middleware(
+            "allowlist",
+            cache.clone(),
+            rate_limits.ip,
+        ))
+        .and(signed_body_middleware(
+            cache.clone(),
+            sig_config,
+            blocklist,
+            body_limit,
+        ))
+        .and(sender_rate_limit_middleware(
+            "allowlist",
+            cache.clone(),
+            rate_limits.sender,
+        ))
+        .and(address_owner_middleware())
+        .and_then(|body: Bytes| async move {
+            serde_json::from_slice::<AllowRequest>(&body)
+                .map_err(|_| warp::reject::custom(ValenceRejection::InvalidBody))
+        })
+        .and(warp::header::headers_cloned())
+        .and(with_node_component(db))
+        .and_then(move |request: AllowRequest, headers, db| {
+            debug!("ALLOW_SENDER requested");
+            map_api_res(allow_sender_handler(headers, request, db))
+        })
+        .with(post_cors())
+}
+
+/// DELETE /allowlist/{sender}
+///
+/// Removes a sender from an address's allowlist. The request's public key must own the address
+///
+/// ### Arguments
+///
+/// * `db` - The database connection to use
+/// * `cache` - The cache connection to use
+/// * `sig_config` - Request signing settings
+/// * `rate_limits` - Rate limits for the route
+/// * `blocklist` - Blocked addresses and public keys
+pub fn disallow_sender<
+    D: KvStoreConnection + Clone + Send + 'static,
+    C: KvStoreConnection + CacheHandler + Clone + Send + 'static,
+>(
+    db: Arc<Mutex<D>>,
+    cache: Arc<Mutex<C>>,
+    sig_config: SignatureConfig,
+    rate_limits: RouteRateLimits,
+    blocklist: BlocklistConnection,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    debug!("Setting up disallow_sender route");
+
+    warp::path("allowlist")
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(ip_rate_limit_middleware(
+            "allowlist",
+            cache.clone(),
+            rate_limits.ip,
+        ))
+        .and(signed_request_middleware(
+            cache.clone(),
+            sig_config,
+            blocklist,
+        ))
+        .and(sender_rate_limit_middleware(
+            "allowlist",
+            cache.clone(),
+            rate_limits.sender,
+        ))
+        .and(address_owner_middleware())
+        .and(warp::header::headers_cloned())
+        .and(with_node_component(db))
+        .and_then(move |sender: String, _, headers, db| {
+            debug!("DISALLOW_SENDER requested");
+            map_api_res(disallow_sender_handler(headers, sender, db))
+        })
+        .with(get_cors())
+}
+
+// ========== ADMIN ROUTES ========== //
+
+/// GET /admin/stats
+///
+/// Retrieves filter and storage statistics. Requires the operator's admin key
+///
+/// ### Arguments
+///
+/// * `db` - The database connection to use
+/// * `cache` - The cache connection to use
+/// * `filter` - The membership filter connection to use
+/// * `admin_key` - The operator key from config
+pub fn stats<
+    D: KvStoreConnection + Clone + Send + 'static,
+    C: KvStoreConnection + Clone + Send + 'static,
+>(
+    db: Arc<Mutex<D>>,
+    cache: Arc<Mutex<C>>,
+    filter: FilterConnection,
+    admin_key: Option<String>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    debug!("Setting up stats route");
+
+    warp::path("admin")
+        .and(warp::path("stats"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(admin_auth_middleware(admin_key))
+        .and(with_node_component(cache))
+        .and(with_node_component(db))
+        .and(with_node_component(filter))
+        .and_then(move |_, cache, db, filter| {
+            debug!("STATS requested");
+            map_api_res(stats_handler(db, cache, filter))
+        })
+}
+
+/// DELETE /admin/addresses/{address}
+///
+/// Purges all data held for an address. Requires the operator's admin key
+///
+/// ### Arguments
+///
+/// * `db` - The database connection to use
+/// * `cache` - The cache connection to use
+/// * `filter` - The membership filter connection to use
+/// * `admin_key` - The operator key from config
+pub fn purge_address<
+    D: KvStoreConnection + Clone + Send + 'static,
+    C: KvStoreConnection + Clone + Send + 'static,
+>(
+    db: Arc<Mutex<D>>,
+    cache: Arc<Mutex<C>>,
+    filter: FilterConnection,
+    admin_key: Option<String>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    debug!("Setting up purge_address route");
+
+    warp::path("admin")
+        .and(warp::path("addresses"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and_then(|address: String| async move {
+            percent_decode_str(&address)
+                .decode_utf8()
+                .map(|address| address.to_string())
+                .map_err(|_| warp::reject::not_found())
+        })
+        .and(warp::delete())
+        .and(admin_auth_middleware(admin_key))
+        .and(with_node_component(cache))
+        .and(with_node_component(db))
+        .and(with_node_component(filter))
+        .and_then(move |address: String, _, cache, db, filter| {
+            debug!("PURGE_ADDRESS requested");
+            let event = AuditEvent {
+                action: AuditAction::Delete,
+                actor: ADMIN_ACTOR.to_string(),
+                address: address.clone(),
+                data_id: None,
+            };
+            map_api_res(audited(
+                event,
+                purge_address_handler(address, db, cache, filter),
+            ))
+        })
+}
+
+/// POST /admin/filter/rebuild
+///
+/// Rebuilds the membership filter from the addresses in the DB, under a new filter
+/// secret if `?rotate=true` is given. Requires the operator's admin key
+///
+/// ### Arguments
+///
+/// * `db` - The database connection to use
+/// * `filter` - The membership filter connection to use
+/// * `admin_key` - The operator key from config
+/// * `secret_configured` - Whether the filter secret is set in config, which prevents rotation
+pub fn rebuild_filter<D: KvStoreConnection + Clone + Send + 'static>(
+    db: Arc<Mutex<D>>,
+    filter: FilterConnection,
+    admin_key: Option<String>,
+    secret_configured: bool,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    debug!("Setting up rebuild_filter route");
+
+    warp::path("admin")
+        .and(warp::path("filter"))
+        .and(warp::path("rebuild"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(admin_auth_middleware(admin_key))
+        .and(warp::query::<RebuildQuery>())
+        .and(with_node_component(db))
+        .and(with_node_component(filter))
+        .and_then(move |_, query: RebuildQuery, db, filter| {
+            debug!("REBUILD_FILTER requested");
+            map_api_res(rebuild_filter_handler(
+                db,
+                filter,
+                query.rotate,
+                secret_configured,
+            ))
+        })
+}
+
+/// GET /admin/config
+///
+/// Retrieves the node's config, without secrets. Requires the operator's admin key
+///
+/// ### Arguments
+///
+/// * `config` - View of the loaded config
+/// * `admin_key` - The operator key from config
+pub fn inspect_config(
+    config: ConfigView,
+    admin_key: Option<String>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    debug!("Setting up inspect_config route");
+
+    warp::path("admin")
+        .and(warp::path("config"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(admin_auth_middleware(admin_key))
+        .and(with_node_component(config))
+        .and_then(move |_, config| {
+            debug!("CONFIG requested");
+            map_api_res(inspect_config_handler(config))
+        })
+}
+
+/// GET /admin/blocklist
+///
+/// Retrieves the blocked addresses and public keys. Requires the operator's admin key
+///
+/// ### Arguments
+///
+/// * `blocklist` - Blocked addresses and public keys
+/// * `admin_key` - The operator key from config
+pub fn list_blocklist(
+    blocklist: BlocklistConnection,
+    admin_key: Option<String>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    debug!("Setting up list_blocklist route");
+
+    warp::path("admin")
+        .and(warp::path("blocklist"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(admin_auth_middleware(admin_key))
+        .and(with_node_component(blocklist))
+        .and_then(move |_, blocklist| {
+            debug!("BLOCKLIST requested");
+            map_api_res(list_blocklist_handler(blocklist))
+        })
+}
+
+/// POST /admin/blocklist
+///
+/// Blocks an address or public key on all routes. Requires the operator's admin key
+///
+/// ### Arguments
+///
+/// * `db` - The database connection to use
+/// * `blocklist` - Blocked addresses and public keys
+/// * `admin_key` - The operator key from config
+pub fn block<D: KvStoreConnection + Clone + Send + 'static>(
+    db: Arc<Mutex<D>>,
+    blocklist: BlocklistConnection,
+    admin_key: Option<String>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    debug!("Setting up block route");
+
+    warp::path("admin")
+        .and(warp::path("blocklist"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(admin_auth_middleware(admin_key))
+        .and(warp::body::json())
+        .and(with_node_component(db))
+        .and(with_node_component(blocklist))
+        .and_then(move |_, request: BlockRequest, db, blocklist| {
+            debug!("BLOCK requested");
+            map_api_res(block_handler(request, db, blocklist))
+        })
+}
+
+/// DELETE /admin/blocklist/{kind}/{value}
+///
+/// Unblocks an address or public key. Requires the operator's admin key
+///
+/// ### Arguments
+///
+/// * `db` - The database connection to use
+/// * `blocklist` - Blocked addresses and public keys
+/// * `admin_key` - The operator key from config
+pub fn unblock<D: KvStoreConnection + Clone + Send + 'static>(
+    db: Arc<Mutex<D>>,
+    blocklist: BlocklistConnection,
+    admin_key: Option<String>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    debug!("Setting up unblock route");
+
+    warp::path("admin")
+        .and(warp::path("blocklist"))
+        .and(warp::path::param::<BlockKind>())
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and_then(|kind: BlockKind, value: String| async move {
+            percent_decode_str(&value)
+                .decode_utf8()
+                .map(|value| (kind, value.to_string()))
+                .map_err(|_| warp::reject::not_found())
+        })
+        .untuple_one()
+        .and(warp::delete())
+        .and(admin_auth_middleware(admin_key))
+        .and(with_node_component(db))
+        .and(with_node_component(blocklist))
+        .and_then(move |kind, value, _, db, blocklist| {
+            debug!("UNBLOCK requested");
+            map_api_res(unblock_handler(kind, value, db, blocklist))
+        })
+}
+
+// ========== HEALTH ROUTES ========== //
+
+/// GET /healthz
+///
+/// Liveness check, answering as long as the node is running
+pub fn healthz() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    debug!("Setting up healthz route");
+
+    warp::path("healthz")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and_then(|| map_api_res(healthz_handler()))
+}
+
+/// GET /readyz
+///
+/// Readiness check, pinging the DB and cache and checking the membership
+/// filter. Answers `503 Service Unavailable` if any of them is not ready
+///
+/// ### Arguments
+///
+/// * `db` - The database connection to use
+/// * `cache` - The cache connection to use
+/// * `filter` - The membership filter connection
+pub fn readyz<
+    D: KvStoreConnection + Clone + Send + 'static,
+    C: KvStoreConnection + Clone + Send + 'static,
+>(
+    db: Arc<Mutex<D>>,
+    cache: Arc<Mutex<C>>,
+    filter: FilterConnection,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    debug!("Setting up readyz route");
+
+    warp::path("readyz")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_node_component(db))
+        .and(with_node_component(cache))
+        .and(with_node_component(filter))
+        .and_then(move |db, cache, filter| map_api_res(readyz_handler(db, cache, filter)))
+}
+
+// ========== METRICS ROUTES ========== //
+
+/// GET /metrics
+///
+/// Exports the node's metrics in the Prometheus text format. Requires the operator's admin key
+///
+/// ### Arguments
+///
+/// * `filter` - Membership filter connection
+/// * `admin_key` - The operator key from config
+pub fn metrics(
+    filter: FilterConnection,
+    admin_key: Option<String>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    debug!("Setting up metrics route");
+
+    warp::path("metrics")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(admin_auth_middleware(admin_key))
+        .and(with_node_component(filter))
+        .and_then(|_, filter| metrics_handler(filter))
+}
@@ -1,11 +1,520 @@
-use crate::db::handler::KvStoreConnection;
+use crate::api::errors::ValenceRejection;
+use crate::blocklist::{BlockKind, Blocklist};
+use crate::constants::{
+    MAX_NONCE_LENGTH, MAX_POW_STAMP_LENGTH, NONCE_KEY_PREFIX, RATE_LIMIT_KEY_PREFIX,
+    READINESS_PING_TIMEOUT, SCHEMA_VERSION_FIELD,
+};
+use crate::db::handler::{CacheHandler, KvStoreConnection};
+use crate::db::schema::upgrade_record;
+use crate::interfaces::{
+    AddressUsage, BlocklistConnection, DependencyCheck, KeyringConnection, QuotaConfig, RateLimit,
+    SetSaveData, SignatureConfig,
+};
+use crate::metrics::record_filter_false_lookup;
+use crate::tls::ClientAddr;
+use crate::utils::is_internal_key;
+use chrono::Utc;
 use futures::lock::Mutex;
+use ring::constant_time::verify_slices_are_equal;
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tracing::info;
+use std::time::{Duration, Instant};
+use tracing::{error, info, instrument, warn};
 use valence_core::api::errors::ApiErrorType;
 use valence_core::api::responses::{json_serialize_embed, CallResponse, JsonReply};
+use valence_core::api::utils::handle_rejection as handle_core_rejection;
+use valence_core::api::utils::with_node_component;
+use valence_core::crypto::sha3_256;
+use valence_core::utils::validate_signature;
+use warp::hyper::body::Bytes;
+use warp::hyper::header::{HeaderValue, RETRY_AFTER};
+use warp::hyper::{HeaderMap, Method};
+use warp::path::FullPath;
+use warp::{Filter, Rejection, Reply};
+
+// ========== MIDDLEWARE ========== //
+
+/// Middleware filter to authenticate operator requests against the configured admin key.
+/// All requests are rejected if no admin key is configured
+///
+/// ### Arguments
+///
+/// * `admin_key` - The operator key from config
+pub fn admin_auth_middleware(
+    admin_key: Option<String>,
+) -> impl Filter<Extract = ((),), Error = Rejection> + Clone {
+    warp::header::optional::<String>("admin_key").and_then(move |provided: Option<String>| {
+        let admin_key = admin_key.clone();
+
+        async move {
+            if let (Some(expected), Some(provided)) = (admin_key, provided) {
+                if verify_slices_are_equal(expected.as_bytes(), provided.as_bytes()).is_ok() {
+                    return Ok(());
+                }
+            }
+
+            warn!("Unauthorized admin request");
+            Err(warp::reject::custom(ValenceRejection::Unauthorized))
+        }
+    })
+}
+
+/// Constructs the canonical form of a request, which clients sign.
+///
+/// Each field is on its own line: method, path, hex SHA3-256 digest of the body,
+/// address, timestamp and nonce
+///
+/// ### Arguments
+///
+/// * `method` - HTTP method of the request
+/// * `path` - Path of the request, as sent
+/// * `body` - Raw request body, empty if there is none
+/// * `address` - Address the request is for
+/// * `timestamp` - Unix time in seconds at which the request was signed
+/// * `nonce` - Single-use value chosen by the client
+pub fn construct_canonical_request(
+    method: &str,
+    path: &str,
+    body: &[u8],
+    address: &str,
+    timestamp: &str,
+    nonce: &str,
+) -> String {
+    format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method.to_uppercase(),
+        path,
+        hex::encode(sha3_256::digest(body)),
+        address,
+        timestamp,
+        nonce
+    )
+}
+
+/// Constructs the message signed under the legacy header-only scheme, which covers
+/// neither the route nor the body
+///
+/// ### Arguments
+///
+/// * `address` - Address the request is for
+/// * `timestamp` - Unix time in seconds at which the request was signed
+/// * `nonce` - Single-use value chosen by the client
+pub fn construct_legacy_signable(address: &str, timestamp: &str, nonce: &str) -> String {
+    format!("{}:{}:{}", address, timestamp, nonce)
+}
+
+/// Constructs the address a public key owns: the hex-encoded SHA3-256 digest of the key
+///
+/// ### Arguments
+///
+/// * `public_key` - Hex-encoded public key
+pub fn construct_address(public_key: &str) -> Option<String> {
+    hex::decode(public_key)
+        .ok()
+        .map(|key| hex::encode(sha3_256::digest(&key)))
+}
+
+/// Constructs the challenge a proof-of-work stamp is computed over, binding the
+/// stamp to the sender, recipient, signed timestamp, nonce and body of a single request
+///
+/// ### Arguments
+///
+/// * `sender` - Public key of the sender
+/// * `recipient` - Address the data is for
+/// * `timestamp` - Signed timestamp of the request
+/// * `nonce` - Signed nonce of the request
+/// * `body` - Raw body of the request
+pub fn construct_pow_challenge(
+    sender: &str,
+    recipient: &str,
+    timestamp: &str,
+    nonce: &str,
+    body: &[u8],
+) -> String {
+    format!(
+        "{}:{}:{}:{}:{}",
+        sender,
+        recipient,
+        timestamp,
+        nonce,
+        hex::encode(sha3_256::digest(body))
+    )
+}
+
+/// Verifies a hashcash-style proof-of-work stamp: the SHA3-256 digest of the
+/// challenge and stamp, joined by `:`, must start with `difficulty` zero bits
+///
+/// ### Arguments
+///
+/// * `challenge` - Challenge the stamp was computed over
+/// * `stamp` - Value chosen by the client
+/// * `difficulty` - Number of leading zero bits required
+pub fn verify_pow_stamp(challenge: &str, stamp: &str, difficulty: u8) -> bool {
+    if stamp.is_empty() || stamp.len() > MAX_POW_STAMP_LENGTH {
+        return false;
+    }
+
+    let digest = sha3_256::digest(format!("{}:{}", challenge, stamp).as_bytes());
+    leading_zero_bits(&digest) >= difficulty as u32
+}
+
+/// Counts the leading zero bits of a digest
+///
+/// ### Arguments
+///
+/// * `bytes` - Digest to count
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in bytes {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// Middleware filter to verify the signature of a request without a body.
+/// See `verify_signed_request` for the checks made
+///
+/// ### Arguments
+///
+/// * `cache` - Cache connection, used to remember seen nonces
+/// * `sig_config` - Request signing settings
+/// * `blocklist` - Blocked addresses and public keys
+pub fn signed_request_middleware<C: CacheHandler + Clone + Send + 'static>(
+    cache: Arc<Mutex<C>>,
+    sig_config: SignatureConfig,
+    blocklist: BlocklistConnection,
+) -> impl Filter<Extract = ((),), Error = Rejection> + Clone {
+    warp::method()
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(with_node_component(cache))
+        .and(with_node_component(blocklist))
+        .and_then(
+            move |method: Method,
+                  path: FullPath,
+                  headers: HeaderMap,
+                  cache: Arc<Mutex<C>>,
+                  blocklist: BlocklistConnection| async move {
+                verify_signed_request(
+                    &method,
+                    path.as_str(),
+                    &headers,
+                    &[],
+                    cache,
+                    sig_config,
+                    &blocklist,
+                )
+                .await
+            },
+        )
+}
+
+/// Middleware filter to verify the signature of a request together with its body,
+/// extracting the raw body once verified
+///
+/// ### Arguments
+///
+/// * `cache` - Cache connection, used to remember seen nonces
+/// * `sig_config` - Request signing settings
+/// * `blocklist` - Blocked addresses and public keys
+/// * `body_limit` - The maximum size of the request body
+pub fn signed_body_middleware<C: CacheHandler + Clone + Send + 'static>(
+    cache: Arc<Mutex<C>>,
+    sig_config: SignatureConfig,
+    blocklist: BlocklistConnection,
+    body_limit: u64,
+) -> impl Filter<Extract = (Bytes,), Error = Rejection> + Clone {
+    warp::method()
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::content_length_limit(body_limit))
+        .and(warp::body::bytes())
+        .and(with_node_component(cache))
+        .and(with_node_component(blocklist))
+        .and_then(
+            move |method: Method,
+                  path: FullPath,
+                  headers: HeaderMap,
+                  body: Bytes,
+                  cache: Arc<Mutex<C>>,
+                  blocklist: BlocklistConnection| async move {
+                verify_signed_request(
+                    &method,
+                    path.as_str(),
+                    &headers,
+                    &body,
+                    cache,
+                    sig_config,
+                    &blocklist,
+                )
+                .await
+                .map(|_| body)
+            },
+        )
+}
+
+/// Verifies a signed request and guards it against replay.
+///
+/// The signature must cover the canonical form of the request, or only its headers
+/// if legacy signatures are enabled. Requests timestamped outside of the replay
+/// window are rejected, as are nonces already seen for the address while they
+/// could still be replayed. Requests from blocked public keys or for blocked
+/// addresses are rejected once the signature is verified, before the cache is used
+///
+/// ### Arguments
+///
+/// * `method` - HTTP method of the request
+/// * `path` - Path of the request
+/// * `headers` - Request headers
+/// * `body` - Raw request body
+/// * `cache` - Cache connection, used to remember seen nonces
+/// * `sig_config` - Request signing settings
+/// * `blocklist` - Blocked addresses and public keys
+async fn verify_signed_request<C: CacheHandler + Clone + Send + 'static>(
+    method: &Method,
+    path: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+    cache: Arc<Mutex<C>>,
+    sig_config: SignatureConfig,
+    blocklist: &Blocklist,
+) -> Result<(), Rejection> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|n| n.to_str().ok())
+            .unwrap_or_default()
+    };
+    let (public_key, address, signature) =
+        (header("public_key"), header("address"), header("signature"));
+    let (timestamp, nonce) = (header("timestamp"), header("nonce"));
+
+    // Node state shares the keyspace, so clients may not address it
+    if is_internal_key(address) {
+        warn!("Request for reserved address: {}", address);
+        return Err(warp::reject::custom(ValenceRejection::ReservedAddress));
+    }
+
+    let now = Utc::now().timestamp();
+    let fresh = timestamp
+        .parse::<i64>()
+        .is_ok_and(|t| now.abs_diff(t) <= sig_config.replay_window);
+    if !fresh {
+        warn!("Stale request timestamp: {:?}", timestamp);
+        return Err(warp::reject::custom(ValenceRejection::StaleRequest));
+    }
+
+    if nonce.is_empty()
+        || nonce.len() > MAX_NONCE_LENGTH
+        || !nonce.chars().all(|c| c.is_ascii_alphanumeric())
+    {
+        warn!("Malformed request nonce");
+        return Err(warp::reject::custom(ValenceRejection::ReplayedRequest));
+    }
+
+    let canonical =
+        construct_canonical_request(method.as_str(), path, body, address, timestamp, nonce);
+    let valid = validate_signature(public_key, &canonical, signature)
+        || (sig_config.legacy_signatures
+            && validate_signature(
+                public_key,
+                &construct_legacy_signable(address, timestamp, nonce),
+                signature,
+            ));
+    if !valid {
+        warn!("Invalid signature");
+        return Err(warp::reject::custom(ApiErrorType::InvalidSignature));
+    }
+
+    check_blocklist(blocklist, public_key, address, body)?;
+
+    // A nonce must be remembered until its timestamp leaves the window
+    let nonce_key = format!("{}{}:{}", NONCE_KEY_PREFIX, address, nonce);
+    match cache
+        .lock()
+        .await
+        .claim_key(&nonce_key, 2 * sig_config.replay_window as usize)
+        .await
+    {
+        Ok(true) => Ok(()),
+        Ok(false) => {
+            warn!("Replayed request nonce for address: {:?}", address);
+            Err(warp::reject::custom(ValenceRejection::ReplayedRequest))
+        }
+        Err(e) => {
+            error!("Failed to record request nonce: {:?}", e);
+            Err(warp::reject::custom(ValenceRejection::Unavailable))
+        }
+    }
+}
+
+/// Rejects a request from a blocked public key, or for a blocked address. Both the
+/// address header and the `address` field of a JSON body are checked, as they can differ
+///
+/// ### Arguments
+///
+/// * `blocklist` - Blocked addresses and public keys
+/// * `public_key` - Public key of the sender
+/// * `address` - Address header of the request
+/// * `body` - Raw request body, empty if there is none
+fn check_blocklist(
+    blocklist: &Blocklist,
+    public_key: &str,
+    address: &str,
+    body: &[u8],
+) -> Result<(), Rejection> {
+    let body_address = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|b| b.get("address")?.as_str().map(|a| a.to_string()));
+
+    let blocked = blocklist.is_blocked(BlockKind::PublicKey, public_key)
+        || blocklist.is_blocked(BlockKind::Address, address)
+        || body_address.is_some_and(|a| blocklist.is_blocked(BlockKind::Address, &a));
+    if blocked {
+        warn!("Blocked request for address: {:?}", address);
+        return Err(warp::reject::custom(ValenceRejection::Blocked));
+    }
+
+    Ok(())
+}
+
+/// Middleware filter to ensure the request's address is owned by its public key.
+/// Runs after signature verification, which proves the sender holds the key
+pub fn address_owner_middleware() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("public_key")
+        .and(warp::header::optional::<String>("address"))
+        .and_then(
+            |public_key: Option<String>, address: Option<String>| async move {
+                let owned = public_key
+                    .as_deref()
+                    .and_then(construct_address)
+                    .is_some_and(|owned| Some(owned) == address);
+                if !owned {
+                    warn!("Public key does not own address: {:?}", address);
+                    return Err(warp::reject::custom(ValenceRejection::NotAddressOwner));
+                }
+                Ok(())
+            },
+        )
+        .untuple_one()
+}
+
+/// Middleware filter to apply a route's rate limit per client IP. Runs before
+/// signature verification, so that unsigned floods are turned away cheaply
+///
+/// ### Arguments
+///
+/// * `route` - Name of the route, which scopes its buckets
+/// * `cache` - Cache connection holding the buckets
+/// * `limit` - The route's limit per client IP
+pub fn ip_rate_limit_middleware<C: CacheHandler + Clone + Send + 'static>(
+    route: &'static str,
+    cache: Arc<Mutex<C>>,
+    limit: Option<RateLimit>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::addr::remote()
+        .and(warp::ext::optional::<ClientAddr>())
+        .map(|remote: Option<SocketAddr>, client: Option<ClientAddr>| {
+            remote.or(client.map(|c| c.0))
+        })
+        .and(with_node_component(cache))
+        .and_then(
+            move |remote: Option<SocketAddr>, cache: Arc<Mutex<C>>| async move {
+                let id = remote.map(|r| format!("ip:{}", r.ip()));
+                take_rate_limit_token(route, cache, limit, id).await
+            },
+        )
+        .untuple_one()
+}
+
+/// Middleware filter to apply a route's rate limit per sender public key. Runs after
+/// signature verification, so that only the holder of a key can drain its bucket
+///
+/// ### Arguments
+///
+/// * `route` - Name of the route, which scopes its buckets
+/// * `cache` - Cache connection holding the buckets
+/// * `limit` - The route's limit per sender
+pub fn sender_rate_limit_middleware<C: CacheHandler + Clone + Send + 'static>(
+    route: &'static str,
+    cache: Arc<Mutex<C>>,
+    limit: Option<RateLimit>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("public_key")
+        .and(with_node_component(cache))
+        .and_then(
+            move |public_key: Option<String>, cache: Arc<Mutex<C>>| async move {
+                let id = public_key.map(|k| format!("sender:{}", k));
+                take_rate_limit_token(route, cache, limit, id).await
+            },
+        )
+        .untuple_one()
+}
+
+/// Takes a token from a rate limit bucket. Requests are refused if the bucket
+/// cannot be read, rather than let through unlimited
+///
+/// ### Arguments
+///
+/// * `route` - Name of the route, which scopes its buckets
+/// * `cache` - Cache connection holding the buckets
+/// * `limit` - Capacity and refill rate of the bucket, if the route is limited
+/// * `id` - Identity the bucket belongs to, if known
+async fn take_rate_limit_token<C: CacheHandler + Clone + Send + 'static>(
+    route: &str,
+    cache: Arc<Mutex<C>>,
+    limit: Option<RateLimit>,
+    id: Option<String>,
+) -> Result<(), Rejection> {
+    let (Some(limit), Some(id)) = (limit, id) else {
+        return Ok(());
+    };
+
+    let key = format!("{}{}:{}", RATE_LIMIT_KEY_PREFIX, route, id);
+    match cache.lock().await.take_token(&key, limit).await {
+        Ok(0) => Ok(()),
+        Ok(wait) => {
+            warn!("Rate limited {} request", route);
+            Err(warp::reject::custom(ValenceRejection::RateLimited {
+                retry_after: wait.div_ceil(1000),
+            }))
+        }
+        Err(e) => {
+            error!("Failed to apply rate limit: {:?}", e);
+            Err(warp::reject::custom(ValenceRejection::Unavailable))
+        }
+    }
+}
+
+/// Rejection handler, covering Valence's own rejections before deferring to the core handler
+///
+/// ### Arguments
+///
+/// * `err` - Rejection error
+pub async fn handle_rejection(err: Rejection) -> Result<warp::reply::Response, Infallible> {
+    if let Some(rejection) = err.find::<ValenceRejection>() {
+        let mut response =
+            warp::reply::with_status(rejection.to_string(), rejection.status()).into_response();
+        if let Some(retry_after) = rejection.retry_after() {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        return Ok(response);
+    }
+
+    handle_core_rejection(err)
+        .await
+        .map(|reply| reply.into_response())
+}
+
+// ========== DB UTILS ========== //
 
 /// Retrieve data from the database
 ///
@@ -14,10 +523,13 @@ use valence_core::api::responses::{json_serialize_embed, CallResponse, JsonReply
 /// * `db` - Database connection
 /// * `address` - Address to retrieve data from
 /// * `value_id` - Value ID to retrieve (Optional, if not provided, all values for the address are retrieved)
+/// * `keyring` - Keys to decrypt the data with, if encryption at rest is enabled
+#[instrument(level = "debug", skip_all)]
 pub async fn retrieve_from_db<D: KvStoreConnection + Clone + Send + 'static>(
     db: Arc<Mutex<D>>,
     address: &str,
     value_id: Option<&str>,
+    keyring: &KeyringConnection,
 ) -> Result<JsonReply, JsonReply> {
     let r = CallResponse::new("get_data");
     info!("RETRIEVE_FROM_DB requested with address: {:?}", address);
@@ -27,11 +539,19 @@ pub async fn retrieve_from_db<D: KvStoreConnection + Clone + Send + 'static>(
 
     match db_result {
         Ok(data) => match data {
-            Some(value) => {
-                return r.into_ok("Data retrieved successfully", json_serialize_embed(value));
-            }
+            Some(value) => match read_entries(keyring, address, value) {
+                Ok(value) => r.into_ok("Data retrieved successfully", json_serialize_embed(value)),
+                Err(e) => {
+                    error!("Failed to decrypt data for address {}: {}", address, e);
+                    r.into_err_internal(ApiErrorType::DataDeserializationFailed)
+                }
+            },
             None => {
-                return r.into_err_internal(ApiErrorType::DataNotFound);
+                // The filter passed an address with no data at all
+                if value_id.is_none() {
+                    record_filter_false_lookup();
+                }
+                r.into_err_internal(ApiErrorType::DataNotFound)
             }
         },
         Err(_) => r.into_err_internal(ApiErrorType::DBQueryFailed),
@@ -45,6 +565,7 @@ pub async fn retrieve_from_db<D: KvStoreConnection + Clone + Send + 'static>(
 /// * `db` - Database connection
 /// * `address` - Address to retrieve data from
 /// * `value_id` - Value ID to delete (Optional, if not provided, all values for the address are deleted)
+#[instrument(level = "debug", skip_all)]
 pub async fn delete_from_db<D: KvStoreConnection + Clone + Send + 'static>(
     db: Arc<Mutex<D>>,
     address: &str,
@@ -85,3 +606,160 @@ pub fn serialize_all_entries(data: HashMap<String, String>) -> HashMap<String, V
     }
     output
 }
+
+/// Decrypts the data of a stored entry. Entries are returned as is if
+/// encryption at rest is disabled
+///
+/// ### Arguments
+///
+/// * `keyring` - Keys to decrypt the data with, if encryption at rest is enabled
+/// * `address` - Address the entry is stored under
+/// * `data_id` - Value ID the entry is stored under
+/// * `entry` - Entry as stored
+pub fn decrypt_entry(
+    keyring: &KeyringConnection,
+    address: &str,
+    data_id: &str,
+    mut entry: Value,
+) -> Result<Value, String> {
+    if let (Some(keyring), Some(data)) = (keyring, entry.get_mut("data")) {
+        *data = keyring.decrypt(data.take(), address, data_id)?;
+    }
+    Ok(entry)
+}
+
+/// Prepares a stored entry to be returned, upgrading it to the current schema,
+/// leaving out its schema version and decrypting its data
+///
+/// ### Arguments
+///
+/// * `keyring` - Keys to decrypt the data with, if encryption at rest is enabled
+/// * `address` - Address the entry is stored under
+/// * `data_id` - Value ID the entry is stored under
+/// * `entry` - Entry as stored
+pub fn read_entry(
+    keyring: &KeyringConnection,
+    address: &str,
+    data_id: &str,
+    mut entry: Value,
+) -> Result<Value, String> {
+    upgrade_record::<SetSaveData>(&mut entry)?;
+    if let Some(fields) = entry.as_object_mut() {
+        fields.remove(SCHEMA_VERSION_FIELD);
+    }
+    decrypt_entry(keyring, address, data_id, entry)
+}
+
+/// Prepares all stored entries to be returned
+///
+/// ### Arguments
+///
+/// * `keyring` - Keys to decrypt the data with, if encryption at rest is enabled
+/// * `address` - Address the entries are stored under
+/// * `entries` - Entries as stored, by value ID
+pub fn read_entries(
+    keyring: &KeyringConnection,
+    address: &str,
+    entries: HashMap<String, Value>,
+) -> Result<HashMap<String, Value>, String> {
+    entries
+        .into_iter()
+        .map(|(id, entry)| {
+            let entry = read_entry(keyring, address, &id, entry)?;
+            Ok((id, entry))
+        })
+        .collect()
+}
+
+// ========== HEALTH UTILS ========== //
+
+/// Pings a store, timing how long it takes to answer. Stores which take
+/// longer than the readiness timeout are reported as not ready
+///
+/// ### Arguments
+///
+/// * `store` - The store to ping
+pub async fn check_store<T: KvStoreConnection + Send>(store: Arc<Mutex<T>>) -> DependencyCheck {
+    let started = Instant::now();
+    let result = tokio::time::timeout(Duration::from_millis(READINESS_PING_TIMEOUT), async {
+        store.lock().await.ping().await
+    })
+    .await;
+    let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let error = match result {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some(format!("No answer within {}ms", READINESS_PING_TIMEOUT)),
+    };
+
+    DependencyCheck {
+        ready: error.is_none(),
+        latency_ms,
+        error,
+    }
+}
+
+// ========== QUOTA UTILS ========== //
+
+/// Computes the storage used by an address's entries, in total and per sender.
+/// Sizes are of the data as stored, so include any encryption overhead
+///
+/// ### Arguments
+///
+/// * `entries` - Entries held for the address, by value ID
+/// * `quota` - Quota to report alongside the usage
+pub fn compute_usage(entries: &HashMap<String, SetSaveData>, quota: QuotaConfig) -> AddressUsage {
+    let mut usage = AddressUsage {
+        quota,
+        ..Default::default()
+    };
+
+    for entry in entries.values() {
+        let bytes = serde_json::to_vec(&entry.data).map_or(0, |d| d.len() as u64);
+        let sender = entry.sender.clone().unwrap_or_default();
+        for counter in [&mut usage.total, usage.senders.entry(sender).or_default()] {
+            counter.entries += 1;
+            counter.bytes += bytes;
+        }
+    }
+
+    usage
+}
+
+/// Checks an address's usage against its quota, returning the first limit exceeded
+///
+/// ### Arguments
+///
+/// * `usage` - Storage used by the address
+/// * `sender` - Public key of the sender whose own usage to check
+pub fn check_quota(usage: &AddressUsage, sender: &str) -> Result<(), String> {
+    let quota = usage.quota;
+    let sender_usage = usage.senders.get(sender).copied().unwrap_or_default();
+
+    let checks = [
+        ("entries", usage.total.entries, quota.max_entries),
+        ("bytes", usage.total.bytes, quota.max_bytes),
+        (
+            "entries from sender",
+            sender_usage.entries,
+            quota.max_entries_per_sender,
+        ),
+        (
+            "bytes from sender",
+            sender_usage.bytes,
+            quota.max_bytes_per_sender,
+        ),
+    ];
+
+    for (name, used, limit) in checks {
+        if let Some(limit) = limit.filter(|limit| used > *limit) {
+            return Err(format!(
+                "Storage quota exceeded: {} {} of {} allowed",
+                used, name, limit
+            ));
+        }
+    }
+
+    Ok(())
+}
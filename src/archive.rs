@@ -0,0 +1,284 @@
+use crate::constants::{ALLOWLIST_KEY_PREFIX, ARCHIVE_FORMAT, ARCHIVE_VERSION, BLOCKLIST_KEY};
+use crate::db::handler::KvStoreConnection;
+use crate::filter::sharded::ShardedFilter;
+use crate::utils::{is_internal_key, rebuild_filter_in_place};
+use chrono::{SecondsFormat, Utc};
+use futures::lock::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+// ========== ARCHIVE FORMAT ========== //
+
+/// First line of an archive, describing where it came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveHeader {
+    pub format: String,
+    pub version: u32,
+    /// Version of the node which wrote the archive
+    pub node_version: String,
+    /// RFC 3339 time the export started
+    pub exported_at: String,
+}
+
+impl ArchiveHeader {
+    /// Constructs the header for an archive written now
+    pub fn new() -> Self {
+        ArchiveHeader {
+            format: ARCHIVE_FORMAT.to_string(),
+            version: ARCHIVE_VERSION,
+            node_version: env!("CARGO_PKG_VERSION").to_string(),
+            exported_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+impl Default for ArchiveHeader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A key and all of its entries, making up each line of an archive after the header
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveRecord {
+    pub key: String,
+    /// Unix time in seconds at which the key expires, if it does
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Entries by ID, exactly as stored, so that senders and encryption envelopes are kept
+    pub entries: BTreeMap<String, Value>,
+}
+
+/// Counts of what an export or import went through
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArchiveSummary {
+    pub keys: usize,
+    pub entries: usize,
+    /// Keys left out, because they expired or are not data that is archived
+    pub skipped: usize,
+}
+
+/// Whether a key is carried in archives. Address data, allowlists and the
+/// blocklist are, while the filter is rebuilt on import and nonces and rate
+/// limits are short-lived
+///
+/// ### Arguments
+///
+/// * `key` - Key to check
+pub fn is_archived_key(key: &str) -> bool {
+    !is_internal_key(key) || key.starts_with(ALLOWLIST_KEY_PREFIX) || key == BLOCKLIST_KEY
+}
+
+// ========== EXPORT ========== //
+
+/// Writes every archived key in the DB, with its entries and expiry, as a JSONL archive
+///
+/// ### Arguments
+///
+/// * `db` - The database connection
+/// * `writer` - Where to write the archive
+pub async fn export_archive<T: KvStoreConnection, W: Write>(
+    db: Arc<Mutex<T>>,
+    writer: &mut W,
+) -> Result<ArchiveSummary, String> {
+    let write_err = |e: std::io::Error| format!("Failed to write archive with error: {}", e);
+    let mut summary = ArchiveSummary::default();
+
+    let mut keys = db
+        .lock()
+        .await
+        .get_keys()
+        .await
+        .map_err(|e| format!("Failed to list keys in DB with error: {}", e))?;
+    keys.sort();
+
+    write_line(writer, &ArchiveHeader::new()).map_err(write_err)?;
+
+    for key in keys {
+        if !is_archived_key(&key) {
+            continue;
+        }
+
+        // Lock per key, so that a running node is only held up briefly
+        let mut db_lock = db.lock().await;
+        let entries = db_lock
+            .get_data::<Value>(&key, None)
+            .await
+            .map_err(|e| format!("Failed to read {} from DB with error: {}", key, e))?;
+        let expires_at = db_lock
+            .get_expiry(&key)
+            .await
+            .map_err(|e| format!("Failed to read expiry of {} with error: {}", key, e))?;
+        drop(db_lock);
+
+        // The key was deleted since it was listed
+        let Some(entries) = entries else {
+            summary.skipped += 1;
+            continue;
+        };
+
+        let record = ArchiveRecord {
+            key,
+            expires_at,
+            entries: entries.into_iter().collect(),
+        };
+        write_line(writer, &record).map_err(write_err)?;
+
+        summary.keys += 1;
+        summary.entries += record.entries.len();
+    }
+
+    writer.flush().map_err(write_err)?;
+    Ok(summary)
+}
+
+/// Writes a value as a single line of JSON
+///
+/// ### Arguments
+///
+/// * `writer` - Where to write the line
+/// * `value` - Value to write
+fn write_line<W: Write, V: Serialize>(writer: &mut W, value: &V) -> std::io::Result<()> {
+    serde_json::to_writer(&mut *writer, value)?;
+    writer.write_all(b"\n")
+}
+
+// ========== IMPORT ========== //
+
+/// Reads a JSONL archive into the DB, merging its entries into any held under
+/// the same keys. Keys which have expired are skipped, and the cache's copies
+/// of imported keys are dropped so that they are read afresh from the DB
+///
+/// ### Arguments
+///
+/// * `reader` - Where to read the archive from
+/// * `db` - The database connection
+/// * `cache` - The cache connection
+pub async fn import_archive<D: KvStoreConnection, C: KvStoreConnection, R: BufRead>(
+    reader: R,
+    db: Arc<Mutex<D>>,
+    cache: Arc<Mutex<C>>,
+) -> Result<ArchiveSummary, String> {
+    let mut summary = ArchiveSummary::default();
+    let mut lines = reader.lines().enumerate();
+
+    let header: ArchiveHeader = match lines.next() {
+        Some((_, line)) => {
+            let line = line.map_err(|e| format!("Failed to read archive with error: {}", e))?;
+            serde_json::from_str(&line)
+                .map_err(|e| format!("Archive has no valid header: {}", e))?
+        }
+        None => return Err("Archive is empty".to_string()),
+    };
+    if header.format != ARCHIVE_FORMAT || header.version > ARCHIVE_VERSION {
+        return Err(format!(
+            "Unsupported archive format {} version {}",
+            header.format, header.version
+        ));
+    }
+    info!(
+        "Importing archive exported at {} by node version {}",
+        header.exported_at, header.node_version
+    );
+
+    let now = Utc::now().timestamp().max(0) as u64;
+    for (index, line) in lines {
+        let line = line.map_err(|e| format!("Failed to read archive with error: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: ArchiveRecord = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid record on line {} of archive: {}", index + 1, e))?;
+
+        if !is_archived_key(&record.key) {
+            warn!("Skipping {}, which is not archived data", record.key);
+            summary.skipped += 1;
+            continue;
+        }
+        if record.expires_at.is_some_and(|expiry| expiry <= now) {
+            summary.skipped += 1;
+            continue;
+        }
+
+        let mut db_lock = db.lock().await;
+        for (value_id, value) in &record.entries {
+            let result = match record.expires_at {
+                Some(expiry) => {
+                    db_lock
+                        .set_data_with_expiry(
+                            &record.key,
+                            value_id,
+                            value.clone(),
+                            (expiry - now) as usize,
+                        )
+                        .await
+                }
+                None => db_lock.set_data(&record.key, value_id, value.clone()).await,
+            };
+            result.map_err(|e| format!("Failed to import {} with error: {}", record.key, e))?;
+        }
+        drop(db_lock);
+
+        if let Err(e) = cache.lock().await.del_data(&record.key, None).await {
+            warn!("Failed to drop cached copy of {}: {}", record.key, e);
+        }
+
+        summary.keys += 1;
+        summary.entries += record.entries.len();
+    }
+
+    Ok(summary)
+}
+
+// ========== COMMANDS ========== //
+
+/// Runs `valence export`, writing the DB's data to an archive file
+///
+/// ### Arguments
+///
+/// * `path` - Path of the archive to write
+/// * `db` - The database connection
+pub async fn run_export<T: KvStoreConnection>(path: &str, db: Arc<Mutex<T>>) -> Result<(), String> {
+    let file = File::create(path)
+        .map_err(|e| format!("Failed to create archive {} with error: {}", path, e))?;
+    let summary = export_archive(db, &mut BufWriter::new(file)).await?;
+
+    info!(
+        "Exported {} entries under {} keys to {}",
+        summary.entries, summary.keys, path
+    );
+    Ok(())
+}
+
+/// Runs `valence import`, reading an archive file into the DB and rebuilding
+/// the membership filter from the result
+///
+/// ### Arguments
+///
+/// * `path` - Path of the archive to read
+/// * `db` - The database connection
+/// * `cache` - The cache connection
+/// * `filter` - The membership filter
+pub async fn run_import<D: KvStoreConnection, C: KvStoreConnection>(
+    path: &str,
+    db: Arc<Mutex<D>>,
+    cache: Arc<Mutex<C>>,
+    filter: &ShardedFilter,
+) -> Result<(), String> {
+    let file = File::open(path)
+        .map_err(|e| format!("Failed to open archive {} with error: {}", path, e))?;
+    let summary = import_archive(BufReader::new(file), db.clone(), cache).await?;
+    rebuild_filter_in_place(filter, db).await?;
+
+    info!(
+        "Imported {} entries under {} keys from {}, skipping {} keys",
+        summary.entries, summary.keys, path, summary.skipped
+    );
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::RwLock;
+use valence_core::crypto::sha3_256;
+
+/// What a blocklist entry blocks
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockKind {
+    /// A recipient address, which can then neither receive nor retrieve data
+    Address,
+    /// A sender's public key, which can then make no requests
+    PublicKey,
+}
+
+impl fmt::Display for BlockKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlockKind::Address => write!(f, "address"),
+            BlockKind::PublicKey => write!(f, "public_key"),
+        }
+    }
+}
+
+impl FromStr for BlockKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "address" => Ok(BlockKind::Address),
+            "public_key" => Ok(BlockKind::PublicKey),
+            _ => Err(format!(
+                "Unknown block kind {s}, expected address or public_key"
+            )),
+        }
+    }
+}
+
+/// A blocked address or public key
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BlockEntry {
+    pub kind: BlockKind,
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub blocked_at: String,
+}
+
+impl BlockEntry {
+    /// ID the entry is stored under
+    pub fn id(&self) -> String {
+        block_id(self.kind, &self.value)
+    }
+}
+
+/// ID a blocked value is stored under. Values are hashed so that any value
+/// can be used as a field name by the DB
+///
+/// ### Arguments
+///
+/// * `kind` - What the value is
+/// * `value` - The blocked address or public key
+pub fn block_id(kind: BlockKind, value: &str) -> String {
+    hex::encode(sha3_256::digest(format!("{kind}:{value}").as_bytes()))
+}
+
+/// In-memory copy of the operator's blocklist, checked on every request
+#[derive(Debug, Default)]
+pub struct Blocklist {
+    entries: RwLock<HashMap<String, BlockEntry>>,
+}
+
+impl Blocklist {
+    /// Constructs a blocklist holding the given entries
+    ///
+    /// ### Arguments
+    ///
+    /// * `entries` - Entries to hold
+    pub fn from_entries(entries: impl IntoIterator<Item = BlockEntry>) -> Self {
+        Blocklist {
+            entries: RwLock::new(entries.into_iter().map(|e| (e.id(), e)).collect()),
+        }
+    }
+
+    /// Whether a value is blocked
+    ///
+    /// ### Arguments
+    ///
+    /// * `kind` - What the value is
+    /// * `value` - Address or public key to check
+    pub fn is_blocked(&self, kind: BlockKind, value: &str) -> bool {
+        self.entries
+            .read()
+            .unwrap()
+            .contains_key(&block_id(kind, value))
+    }
+
+    /// Adds an entry, replacing any existing entry for the same value
+    ///
+    /// ### Arguments
+    ///
+    /// * `entry` - Entry to add
+    pub fn insert(&self, entry: BlockEntry) {
+        self.entries.write().unwrap().insert(entry.id(), entry);
+    }
+
+    /// Removes the entry for a value, returning whether there was one
+    ///
+    /// ### Arguments
+    ///
+    /// * `kind` - What the value is
+    /// * `value` - Address or public key to unblock
+    pub fn remove(&self, kind: BlockKind, value: &str) -> bool {
+        self.entries
+            .write()
+            .unwrap()
+            .remove(&block_id(kind, value))
+            .is_some()
+    }
+
+    /// All entries, ordered by kind and value
+    pub fn entries(&self) -> Vec<BlockEntry> {
+        let mut entries: Vec<_> = self.entries.read().unwrap().values().cloned().collect();
+        entries.sort_by(|a, b| (a.kind as u8, &a.value).cmp(&(b.kind as u8, &b.value)));
+        entries
+    }
+}
@@ -0,0 +1,34 @@
+/// Usage shown when the command line cannot be parsed
+pub const USAGE: &str = "Usage:
+    valence                  Run the node
+    valence export <FILE>    Write all stored data to a JSONL archive
+    valence import <FILE>    Read a JSONL archive into the DB and rebuild the filter";
+
+/// What the node was asked to do on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Serve,
+    Export(String),
+    Import(String),
+}
+
+/// Parses the command line arguments, without the program name
+///
+/// ### Arguments
+///
+/// * `args` - Command line arguments
+pub fn parse_command<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut args = args.into_iter();
+    let command = match args.next().as_deref() {
+        None => return Ok(Command::Serve),
+        Some("export") => Command::Export,
+        Some("import") => Command::Import,
+        Some(other) => return Err(format!("Unknown command: {}", other)),
+    };
+
+    match (args.next(), args.next()) {
+        (Some(path), None) => Ok(command(path)),
+        (None, _) => Err("Missing archive path".to_string()),
+        (Some(_), Some(extra)) => Err(format!("Unexpected argument: {}", extra)),
+    }
+}
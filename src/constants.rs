@@ -1,3 +1,5 @@
+#![allow(clippy::empty_line_after_doc_comments)]
+
 /// ==== CONFIG ==== ///
 
 pub const CONFIG_FILE: &str = "config.toml";
@@ -13,6 +15,20 @@ pub const SETTINGS_CACHE_PORT: &str = "6379";
 pub const SETTINGS_CACHE_PASSWORD: &str = "password";
 pub const SETTINGS_BODY_LIMIT: u64 = 4096;
 pub const SETTINGS_CACHE_TTL: u64 = 600;
+pub const SETTINGS_FILTER_TYPE: &str = "cuckoo";
+pub const SETTINGS_FILTER_SHARDS: usize = 16;
+pub const SETTINGS_REPLAY_WINDOW: u64 = 300;
+pub const SETTINGS_LEGACY_SIGNATURES: bool = false;
+pub const SETTINGS_REENCRYPT_INTERVAL: u64 = 3600;
+pub const SETTINGS_TLS_RELOAD_INTERVAL: u64 = 60;
+pub const SETTINGS_POW_DIFFICULTY: u8 = 0;
+pub const SETTINGS_SHUTDOWN_TIMEOUT: u64 = 30;
+pub const SETTINGS_MIGRATE_ON_STARTUP: bool = false;
+pub const SETTINGS_SWEEP_INTERVAL: u64 = 3600;
+pub const SETTINGS_CONNECT_ATTEMPTS: u32 = 5;
+pub const SETTINGS_OTLP_SERVICE_NAME: &str = "valence";
+pub const SETTINGS_LOG_FORMAT: &str = "text";
+pub const SETTINGS_LOG_ROTATION: &str = "daily";
 
 /// ==== DRUID ==== ///
 
@@ -25,5 +41,152 @@ pub const DRUID_LENGTH: usize = 16;
 /// ==== STORAGE ==== ///
 
 pub const DB_KEY: &str = "default";
-pub const CUCKOO_FILTER_KEY: &str = "cuckoo_filter";
-pub const CUCKOO_FILTER_VALUE_ID: &str = "cuckoo_filter_id";
+pub const FILTER_SHARD_KEY_PREFIX: &str = "membership_filter_shard_";
+pub const FILTER_VALUE_ID: &str = "snapshot";
+
+/// Location of the single unsharded filter, removed on migration
+pub const FILTER_KEY: &str = "membership_filter";
+
+/// Location of filters saved before the snapshot format, removed on migration
+pub const LEGACY_CUCKOO_FILTER_KEY: &str = "cuckoo_filter";
+
+pub const FILTER_SECRET_KEY: &str = "filter_secret";
+pub const FILTER_SECRET_VALUE_ID: &str = "secret";
+
+/// Key holding the operator's blocklist, one value per blocked address or public key
+pub const BLOCKLIST_KEY: &str = "blocklist";
+
+/// Prefix of the DB keys holding each address's allowlist of senders
+pub const ALLOWLIST_KEY_PREFIX: &str = "allowlist:";
+
+/// Prefix of the cache keys recording nonces of signed requests
+pub const NONCE_KEY_PREFIX: &str = "nonce:";
+
+/// Prefix of the cache keys holding rate limiting buckets
+pub const RATE_LIMIT_KEY_PREFIX: &str = "ratelimit:";
+
+/// Field of a stored record holding the schema version of its shape.
+/// Records written before versioning have none, and are at version 0
+pub const SCHEMA_VERSION_FIELD: &str = "_v";
+
+/// Keys used for node state rather than address data
+pub const INTERNAL_KEYS: &[&str] = &[
+    FILTER_KEY,
+    LEGACY_CUCKOO_FILTER_KEY,
+    FILTER_SECRET_KEY,
+    BLOCKLIST_KEY,
+];
+
+/// Number of keys sampled to estimate Redis stats
+pub const REDIS_STATS_SAMPLE_SIZE: usize = 256;
+
+/// ==== CONNECTIONS ==== ///
+
+/// Milliseconds waited before retrying a failed connection at startup, doubled
+/// after each attempt up to the maximum
+pub const CONNECT_BACKOFF_INITIAL: u64 = 500;
+pub const CONNECT_BACKOFF_MAX: u64 = 30_000;
+
+/// Milliseconds a store has to accept a connection and answer a ping
+pub const CONNECT_TIMEOUT: u64 = 5000;
+
+/// Seconds between attempts to reach the cache again while it is unavailable
+pub const CACHE_RECONNECT_INTERVAL: u64 = 5;
+
+/// Error returned by cache operations while the cache is unavailable
+pub const CACHE_UNAVAILABLE: &str = "Cache unavailable";
+
+/// Claims and rate limiting buckets held in memory while the cache is unavailable,
+/// above which those which have lapsed are dropped
+pub const LOCAL_FALLBACK_PRUNE_THRESHOLD: usize = 10_000;
+
+/// ==== REQUESTS ==== ///
+
+pub const MAX_NONCE_LENGTH: usize = 64;
+pub const MAX_POW_STAMP_LENGTH: usize = 64;
+
+/// Highest configurable proof-of-work difficulty, in leading zero bits
+pub const MAX_POW_DIFFICULTY: u8 = 32;
+
+/// ==== FILTER ==== ///
+
+/// Mirrors the bucket layout used by the `cuckoofilter` crate
+pub const CUCKOO_BUCKET_SIZE: usize = 4;
+pub const CUCKOO_FINGERPRINT_BITS: u32 = 8;
+pub const BLOOM_FILTER_CAPACITY: usize = (1 << 20) - 1;
+/// Number of items a sharded filter is sized for across all of its shards
+pub const SHARDED_FILTER_CAPACITY: usize = (1 << 20) - 1;
+pub const BLOOM_FILTER_FP_RATE: f64 = 0.01;
+pub const XOR_FILTER_BATCH_SIZE: usize = 1024;
+pub const FILTER_SNAPSHOT_MAGIC: &[u8; 4] = b"VLFS";
+pub const FILTER_SNAPSHOT_VERSION: u8 = 3;
+pub const FILTER_SECRET_LEN: usize = 16;
+pub const FILTER_SECRET_ID_LEN: usize = 8;
+
+/// ==== TLS ==== ///
+
+/// Seconds a client has to complete the TLS handshake
+pub const TLS_HANDSHAKE_TIMEOUT: u64 = 10;
+
+/// ==== ARCHIVE ==== ///
+
+/// Marks the first line of an export archive
+pub const ARCHIVE_FORMAT: &str = "valence-archive";
+/// Version of the archive format written by `valence export`
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// ==== HEALTH ==== ///
+
+/// Milliseconds a store has to answer a readiness ping
+pub const READINESS_PING_TIMEOUT: u64 = 2000;
+
+/// ==== TRACING ==== ///
+
+/// Name of the tracer the node's spans are created by
+pub const TRACER_NAME: &str = "valence";
+/// Target of the events warp records for each request
+pub const WARP_TRACE_TARGET: &str = "warp::filters::trace";
+/// Header a caller can set to choose the ID its request is logged under
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+pub const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+/// ==== METRICS ==== ///
+
+pub const METRICS_NAMESPACE: &str = "valence";
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Routes labelled individually in request metrics, with any other path labelled `other`
+pub const METRICS_ROUTES: &[&str] = &[
+    "get_data",
+    "set_data",
+    "del_data",
+    "usage",
+    "allowlist",
+    "healthz",
+    "readyz",
+    "metrics",
+    "admin/stats",
+    "admin/addresses",
+    "admin/filter",
+    "admin/config",
+    "admin/blocklist",
+];
+
+/// Histogram buckets for request and filter save durations, in seconds
+pub const REQUEST_DURATION_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// ==== ENCRYPTION ==== ///
+
+pub const ENVELOPE_VERSION: u8 = 1;
+
+/// ==== LOGGING ==== ///
+
+/// Tracing target of the audit log
+pub const AUDIT_TARGET: &str = "audit";
+/// Actor recorded for operations made through the admin routes
+pub const ADMIN_ACTOR: &str = "admin";
+pub const REDACTED: &str = "[REDACTED]";
+pub const SIGNATURE_HEADERS: &[&str] = &["signature"];
+pub const CREDENTIAL_HEADERS: &[&str] = &["admin_key", "authorization", "cookie"];
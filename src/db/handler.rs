@@ -1,3 +1,4 @@
+use crate::interfaces::{RateLimit, StoreStats};
 use async_trait::async_trait;
 use serde::{de::DeserializeOwned, Serialize};
 use std::collections::HashMap;
@@ -5,6 +6,9 @@ use std::collections::HashMap;
 /// Trait for a key-value data store connection
 #[async_trait]
 pub trait KvStoreConnection {
+    /// Name of the store implementation, used to label its metrics
+    const BACKEND: &'static str;
+
     /// Initialize a connection to the cache
     ///
     /// ### Arguments
@@ -67,6 +71,39 @@ pub trait KvStoreConnection {
         key: &str,
         value_id: Option<&str>,
     ) -> Result<Option<HashMap<String, T>>, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Gets the Unix time in seconds at which a key expires, or `None` if it does
+    /// not expire or is not held
+    ///
+    /// ### Arguments
+    ///
+    /// * `key` - Key to look up
+    async fn get_expiry(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<u64>, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Gets all keys held in the store
+    async fn get_keys(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Gets approximate counts of the addresses, entries and bytes held in the store,
+    /// excluding the node's internal keys
+    async fn get_stats(&mut self) -> Result<StoreStats, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Checks that the store can be reached
+    async fn ping(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Whether an error returned by the store means that it could not be reached,
+    /// rather than that the operation failed. Stores which cannot tell the two
+    /// apart count every error
+    ///
+    /// ### Arguments
+    ///
+    /// * `error` - Error returned by the store
+    fn is_unreachable(error: &(dyn std::error::Error + 'static)) -> bool {
+        let _ = error;
+        true
+    }
 }
 
 #[async_trait]
@@ -82,4 +119,31 @@ pub trait CacheHandler {
         key: &str,
         seconds: usize,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Sets a marker key which expires, unless the key already exists.
+    /// Returns whether the key was newly set
+    ///
+    /// ### Arguments
+    ///
+    /// * `key` - Key to claim
+    /// * `seconds` - Number of seconds to hold the claim for
+    async fn claim_key(
+        &mut self,
+        key: &str,
+        seconds: usize,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Takes a token from a rate limiting bucket, refilling it for the time since it
+    /// was last used. Returns the milliseconds until a token is available, or zero
+    /// if one was taken
+    ///
+    /// ### Arguments
+    ///
+    /// * `key` - Key of the bucket
+    /// * `limit` - Capacity and refill rate of the bucket
+    async fn take_token(
+        &mut self,
+        key: &str,
+        limit: RateLimit,
+    ) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>;
 }
@@ -0,0 +1,139 @@
+use crate::db::handler::{CacheHandler, KvStoreConnection};
+use crate::interfaces::{RateLimit, StoreStats};
+use crate::metrics::record_backend_error;
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+
+/// Store which counts the errors returned by the underlying store in the
+/// node's metrics, labelled with the underlying store's backend
+#[derive(Debug, Clone)]
+pub struct MeteredStore<T> {
+    inner: T,
+}
+
+impl<T: KvStoreConnection> MeteredStore<T> {
+    /// Wraps a store
+    ///
+    /// ### Arguments
+    ///
+    /// * `inner` - The store to count errors for
+    pub fn new(inner: T) -> Self {
+        MeteredStore { inner }
+    }
+
+    /// Counts the result of an operation if it failed, passing it through
+    ///
+    /// ### Arguments
+    ///
+    /// * `operation` - Name of the operation
+    /// * `result` - Result of the operation
+    fn metered<R>(
+        operation: &str,
+        result: Result<R, Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Result<R, Box<dyn std::error::Error + Send + Sync>> {
+        if result.is_err() {
+            record_backend_error(T::BACKEND, operation);
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<T: KvStoreConnection + CacheHandler + Send> CacheHandler for MeteredStore<T> {
+    async fn expire_entry(
+        &mut self,
+        key: &str,
+        seconds: usize,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Self::metered("expire_entry", self.inner.expire_entry(key, seconds).await)
+    }
+
+    async fn claim_key(
+        &mut self,
+        key: &str,
+        seconds: usize,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        Self::metered("claim_key", self.inner.claim_key(key, seconds).await)
+    }
+
+    async fn take_token(
+        &mut self,
+        key: &str,
+        limit: RateLimit,
+    ) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        Self::metered("take_token", self.inner.take_token(key, limit).await)
+    }
+}
+
+#[async_trait]
+impl<T: KvStoreConnection + Send> KvStoreConnection for MeteredStore<T> {
+    const BACKEND: &'static str = T::BACKEND;
+
+    async fn init(url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Self::metered("init", T::init(url).await).map(MeteredStore::new)
+    }
+
+    async fn set_data<V: Serialize + Send + DeserializeOwned>(
+        &mut self,
+        key: &str,
+        value_id: &str,
+        value: V,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Self::metered("set_data", self.inner.set_data(key, value_id, value).await)
+    }
+
+    async fn set_data_with_expiry<V: Serialize + DeserializeOwned + Send>(
+        &mut self,
+        key: &str,
+        value_id: &str,
+        value: V,
+        seconds: usize,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Self::metered(
+            "set_data_with_expiry",
+            self.inner
+                .set_data_with_expiry(key, value_id, value, seconds)
+                .await,
+        )
+    }
+
+    async fn del_data(
+        &mut self,
+        key: &str,
+        value_id: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Self::metered("del_data", self.inner.del_data(key, value_id).await)
+    }
+
+    async fn get_data<V: Clone + DeserializeOwned>(
+        &mut self,
+        key: &str,
+        value_id: Option<&str>,
+    ) -> Result<Option<HashMap<String, V>>, Box<dyn std::error::Error + Send + Sync>> {
+        Self::metered("get_data", self.inner.get_data(key, value_id).await)
+    }
+
+    async fn get_expiry(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<u64>, Box<dyn std::error::Error + Send + Sync>> {
+        Self::metered("get_expiry", self.inner.get_expiry(key).await)
+    }
+
+    async fn get_keys(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        Self::metered("get_keys", self.inner.get_keys().await)
+    }
+
+    async fn get_stats(&mut self) -> Result<StoreStats, Box<dyn std::error::Error + Send + Sync>> {
+        Self::metered("get_stats", self.inner.get_stats().await)
+    }
+
+    async fn ping(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Self::metered("ping", self.inner.ping().await)
+    }
+
+    fn is_unreachable(error: &(dyn std::error::Error + 'static)) -> bool {
+        T::is_unreachable(error)
+    }
+}
@@ -1,3 +1,6 @@
 pub mod handler;
+pub mod metered;
 pub mod mongo_db;
 pub mod redis_cache;
+pub mod resilient;
+pub mod schema;
@@ -1,10 +1,14 @@
+use crate::constants::{ALLOWLIST_KEY_PREFIX, FILTER_SHARD_KEY_PREFIX, INTERNAL_KEYS};
+use crate::interfaces::StoreStats;
+use crate::logging::{redact_payload, redact_url};
 use async_trait::async_trait;
-use mongodb::bson::{doc, DateTime, Document};
+use futures::TryStreamExt;
+use mongodb::bson::{doc, Bson, DateTime, Document, Regex};
 use mongodb::{options::ClientOptions, Client};
 use serde::{de::DeserializeOwned, Serialize};
 use std::collections::HashMap;
 use std::fmt::Debug;
-use tracing::{debug, event, span, trace, Level};
+use tracing::{debug, event, instrument, trace, Level};
 
 use super::handler::KvStoreConnection;
 
@@ -20,49 +24,17 @@ pub struct MongoDbConn {
     pub index: MongoDbIndex,
 }
 
-impl MongoDbConn {
-    /// Creates a TTL index on the expiry field.
-    ///
-    /// NOTE: This function will need to be called in the main function when initialising a MongoDB connection.
-    pub async fn create_ttl_index(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let collection = self
-            .client
-            .database(&self.index.db_name)
-            .collection::<Document>(&self.index.coll_name);
-
-        // Create TTL index on the 'expiry' field
-        let index_model = mongodb::IndexModel::builder()
-            .keys(doc! { "expiry": 1 })
-            .options(Some(
-                mongodb::options::IndexOptions::builder()
-                    .expire_after(Some(std::time::Duration::from_secs(0)))
-                    .build(),
-            ))
-            .build();
-
-        collection.create_index(index_model, None).await?;
-        Ok(())
-    }
-}
-
 #[async_trait]
 impl KvStoreConnection for MongoDbConn {
-    async fn init(url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        // Tracing
-        let span = span!(Level::TRACE, "MongoDbConn::init");
-        let _enter = span.enter();
+    const BACKEND: &'static str = "mongodb";
 
-        let client_options = match ClientOptions::parse(url).await {
-            Ok(client_options) => client_options,
-            Err(e) => panic!("Failed to connect to MongoDB instance with error: {e}"),
-        };
+    #[instrument(level = "debug", name = "MongoDbConn::init", skip_all)]
+    async fn init(url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let client_options = ClientOptions::parse(url).await?;
 
-        trace!("Connected to MongoDB instance at {url}");
+        trace!("Connected to MongoDB instance at {}", redact_url(url));
 
-        let client = match Client::with_options(client_options) {
-            Ok(client) => client,
-            Err(e) => panic!("Failed to connect to MongoDB instance with error: {e}"),
-        };
+        let client = Client::with_options(client_options)?;
 
         trace!("MongoDB client created successfully");
 
@@ -74,6 +46,7 @@ impl KvStoreConnection for MongoDbConn {
         Ok(MongoDbConn { client, index })
     }
 
+    #[instrument(level = "debug", name = "MongoDbConn::set_data", skip_all, fields(key = %key))]
     async fn set_data<T: Serialize + std::marker::Send + DeserializeOwned>(
         &mut self,
         key: &str,
@@ -81,9 +54,6 @@ impl KvStoreConnection for MongoDbConn {
         value: T,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         debug!("set_data {:?} / {}", key, value_id);
-        // Tracing
-        let span = span!(Level::TRACE, "MongoDbConn::set_data");
-        let _enter = span.enter();
 
         let collection = self
             .client
@@ -112,7 +82,7 @@ impl KvStoreConnection for MongoDbConn {
 
         // Serialize the vec back to a BSON array
         let serialized_vec = mongodb::bson::to_bson(&mapping)?;
-        debug!("serialized_vec: {:?}", serialized_vec);
+        debug!("serialized_vec: {}", redact_payload(&serialized_vec));
 
         // Create or update the document
         let update = doc! {
@@ -136,15 +106,12 @@ impl KvStoreConnection for MongoDbConn {
         Ok(())
     }
 
+    #[instrument(level = "debug", name = "MongoDbConn::get_data", skip_all, fields(key = %key))]
     async fn get_data<T: DeserializeOwned + Clone>(
         &mut self,
         key: &str,
         value_id: Option<&str>,
     ) -> Result<Option<HashMap<String, T>>, Box<dyn std::error::Error + Send + Sync>> {
-        // Tracing
-        let span = span!(Level::TRACE, "MongoDbConn::get_data");
-        let _enter = span.enter();
-
         let collection = self
             .client
             .database(&self.index.db_name)
@@ -183,6 +150,7 @@ impl KvStoreConnection for MongoDbConn {
         return Ok(None);
     }
 
+    #[instrument(level = "debug", name = "MongoDbConn::set_data_with_expiry", skip_all, fields(key = %key))]
     async fn set_data_with_expiry<T: Serialize + std::marker::Send + DeserializeOwned>(
         &mut self,
         key: &str,
@@ -190,10 +158,6 @@ impl KvStoreConnection for MongoDbConn {
         value: T,
         seconds: usize,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        // Tracing
-        let span = span!(Level::TRACE, "MongoDbConn::set_data_with_expiry");
-        let _enter = span.enter();
-
         let collection = self
             .client
             .database(&self.index.db_name)
@@ -217,7 +181,7 @@ impl KvStoreConnection for MongoDbConn {
         let serialized_vec = mongodb::bson::to_bson(&mapping)?;
 
         // Calculate the expiry time
-        let expiry_time = (seconds * 1000) as i64;
+        let expiry_time = DateTime::now().timestamp_millis() + (seconds * 1000) as i64;
         let expiry_bson_datetime = DateTime::from_millis(expiry_time);
 
         // Create or update the document with the new expiry time
@@ -242,15 +206,12 @@ impl KvStoreConnection for MongoDbConn {
         Ok(())
     }
 
+    #[instrument(level = "debug", name = "MongoDbConn::del_data", skip_all, fields(key = %key))]
     async fn del_data(
         &mut self,
         key: &str,
         value_id: Option<&str>,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        // Tracing
-        let span = span!(Level::TRACE, "MongoDbConn::del_data");
-        let _enter = span.enter();
-
         let collection = self
             .client
             .database(&self.index.db_name)
@@ -269,7 +230,7 @@ impl KvStoreConnection for MongoDbConn {
 
             match collection.find_one_and_update(filter, update, None).await {
                 Ok(result) => {
-                    if let Some(_) = result {
+                    if result.is_some() {
                         // Document was found and updated, log success or handle as needed
                         trace!("Data updated successfully");
                     } else {
@@ -298,4 +259,91 @@ impl KvStoreConnection for MongoDbConn {
 
         Ok(())
     }
+
+    #[instrument(level = "debug", name = "MongoDbConn::get_expiry", skip_all, fields(key = %key))]
+    async fn get_expiry(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<u64>, Box<dyn std::error::Error + Send + Sync>> {
+        let collection = self
+            .client
+            .database(&self.index.db_name)
+            .collection::<Document>(&self.index.coll_name);
+
+        let doc = collection.find_one(doc! { "_id": key }, None).await?;
+
+        Ok(doc
+            .and_then(|doc| doc.get_datetime("expiry").ok().copied())
+            .map(|expiry| (expiry.timestamp_millis() / 1000).max(0) as u64))
+    }
+
+    #[instrument(level = "debug", name = "MongoDbConn::get_keys", skip_all)]
+    async fn get_keys(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let collection = self
+            .client
+            .database(&self.index.db_name)
+            .collection::<Document>(&self.index.coll_name);
+
+        let ids = collection.distinct("_id", None, None).await?;
+
+        Ok(ids
+            .into_iter()
+            .filter_map(|id| id.as_str().map(|k| k.to_string()))
+            .collect())
+    }
+
+    #[instrument(level = "debug", name = "MongoDbConn::get_stats", skip_all)]
+    async fn get_stats(&mut self) -> Result<StoreStats, Box<dyn std::error::Error + Send + Sync>> {
+        let collection = self
+            .client
+            .database(&self.index.db_name)
+            .collection::<Document>(&self.index.coll_name);
+
+        let pipeline = vec![
+            doc! { "$match": { "_id": {
+                "$nin": INTERNAL_KEYS,
+                "$not": Regex {
+                    pattern: format!("^({}|{})", FILTER_SHARD_KEY_PREFIX, ALLOWLIST_KEY_PREFIX),
+                    options: String::new(),
+                },
+            } } },
+            doc! { "$project": {
+                "entries": { "$size": { "$objectToArray": { "$ifNull": ["$data", {}] } } },
+                "bytes": { "$bsonSize": "$$ROOT" },
+            } },
+            doc! { "$group": {
+                "_id": null,
+                "addresses": { "$sum": 1 },
+                "entries": { "$sum": "$entries" },
+                "bytes": { "$sum": "$bytes" },
+            } },
+        ];
+
+        let mut cursor = collection.aggregate(pipeline, None).await?;
+        let totals = match cursor.try_next().await? {
+            Some(totals) => totals,
+            None => return Ok(StoreStats::default()),
+        };
+
+        let count = |field: &str| match totals.get(field) {
+            Some(Bson::Int32(n)) => *n as u64,
+            Some(Bson::Int64(n)) => *n as u64,
+            _ => 0,
+        };
+
+        Ok(StoreStats {
+            addresses: count("addresses"),
+            entries: count("entries"),
+            bytes: count("bytes"),
+        })
+    }
+
+    #[instrument(level = "debug", name = "MongoDbConn::ping", skip_all)]
+    async fn ping(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.client
+            .database(&self.index.db_name)
+            .run_command(doc! { "ping": 1 }, None)
+            .await?;
+        Ok(())
+    }
 }
@@ -1,10 +1,38 @@
 use std::collections::HashMap;
 
+use crate::constants::REDIS_STATS_SAMPLE_SIZE;
 use crate::db::handler::{CacheHandler, KvStoreConnection};
+use crate::interfaces::{RateLimit, StoreStats};
+use crate::utils::is_internal_key;
 use async_trait::async_trait;
+use chrono::Utc;
 use redis::{aio::ConnectionManager, AsyncCommands};
 use serde::{de::DeserializeOwned, Serialize};
-use tracing::{event, span, Level};
+use tracing::{event, instrument, Level};
+
+/// Token bucket update, run atomically so that concurrent requests across
+/// instances share one bucket. Returns the milliseconds to wait for a token
+const TAKE_TOKEN_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(bucket[1]) or capacity
+local ts = tonumber(bucket[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * rate / 1000)
+
+local wait = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+else
+    wait = math.ceil((1 - tokens) * 1000 / rate)
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity * 1000 / rate))
+return wait
+"#;
 
 #[derive(Clone)]
 pub struct RedisCacheConn {
@@ -13,18 +41,55 @@ pub struct RedisCacheConn {
 
 #[async_trait]
 impl CacheHandler for RedisCacheConn {
+    #[instrument(level = "debug", name = "RedisCacheConn::expire_entry", skip_all, fields(key = %key))]
     async fn expire_entry(
         &mut self,
         key: &str,
         seconds: usize,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        self.connection.expire(key, seconds).await?;
+        let _: () = self.connection.expire(key, seconds).await?;
         Ok(())
     }
+
+    #[instrument(level = "debug", name = "RedisCacheConn::claim_key", skip_all, fields(key = %key))]
+    async fn claim_key(
+        &mut self,
+        key: &str,
+        seconds: usize,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(key)
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(seconds)
+            .query_async(&mut self.connection)
+            .await?;
+        Ok(claimed.is_some())
+    }
+
+    #[instrument(level = "debug", name = "RedisCacheConn::take_token", skip_all, fields(key = %key))]
+    async fn take_token(
+        &mut self,
+        key: &str,
+        limit: RateLimit,
+    ) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let wait: u64 = redis::Script::new(TAKE_TOKEN_SCRIPT)
+            .key(key)
+            .arg(limit.capacity)
+            .arg(limit.refill_per_second)
+            .arg(Utc::now().timestamp_millis())
+            .invoke_async(&mut self.connection)
+            .await?;
+        Ok(wait)
+    }
 }
 
 #[async_trait]
 impl KvStoreConnection for RedisCacheConn {
+    const BACKEND: &'static str = "redis";
+
+    #[instrument(level = "debug", name = "RedisCacheConn::init", skip_all)]
     async fn init(url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         let redis_client = redis::Client::open(url)?;
         let redis_connection_manager = ConnectionManager::new(redis_client).await?;
@@ -34,6 +99,7 @@ impl KvStoreConnection for RedisCacheConn {
         })
     }
 
+    #[instrument(level = "debug", name = "RedisCacheConn::set_data", skip_all, fields(key = %key))]
     async fn set_data<T: Serialize + DeserializeOwned + Send>(
         &mut self,
         key: &str,
@@ -54,11 +120,12 @@ impl KvStoreConnection for RedisCacheConn {
         mapping.insert(value_id.to_string(), value);
 
         let serialized = serde_json::to_string(&mapping)?;
-        self.connection.set(key, serialized).await?;
+        let _: () = self.connection.set(key, serialized).await?;
 
         Ok(())
     }
 
+    #[instrument(level = "debug", name = "RedisCacheConn::set_data_with_expiry", skip_all, fields(key = %key))]
     async fn set_data_with_expiry<T: Serialize + DeserializeOwned + Send>(
         &mut self,
         key: &str,
@@ -84,14 +151,15 @@ impl KvStoreConnection for RedisCacheConn {
         let serialized = serde_json::to_string(&mapping)?;
 
         // Set the data back to Redis
-        self.connection.set(key, serialized).await?;
+        let _: () = self.connection.set(key, serialized).await?;
 
         // Set the expiry time for the key
-        self.connection.expire(key, seconds).await?;
+        let _: () = self.connection.expire(key, seconds).await?;
 
         Ok(())
     }
 
+    #[instrument(level = "debug", name = "RedisCacheConn::del_data", skip_all, fields(key = %key))]
     async fn del_data(
         &mut self,
         key: &str,
@@ -104,7 +172,7 @@ impl KvStoreConnection for RedisCacheConn {
                 let mut mapping: HashMap<String, String> = self.get_data(key, None).await?.unwrap();
                 mapping.remove(value_id);
                 let serialized = serde_json::to_string(&mapping)?;
-                self.connection.set(key, serialized).await?;
+                let _: () = self.connection.set(key, serialized).await?;
             }
             return Ok(());
         }
@@ -113,14 +181,12 @@ impl KvStoreConnection for RedisCacheConn {
         Ok(())
     }
 
+    #[instrument(level = "debug", name = "RedisCacheConn::get_data", skip_all, fields(key = %key))]
     async fn get_data<T: Clone + DeserializeOwned>(
         &mut self,
         key: &str,
         value_id: Option<&str>,
     ) -> Result<Option<HashMap<String, T>>, Box<dyn std::error::Error + Send + Sync>> {
-        let span = span!(Level::TRACE, "MongoDbConn::get_data");
-        let _enter = span.enter();
-
         // Check if the key exists
         let exists: bool = self.connection.exists(key).await?;
 
@@ -150,4 +216,117 @@ impl KvStoreConnection for RedisCacheConn {
 
         Ok(None)
     }
+
+    #[instrument(level = "debug", name = "RedisCacheConn::get_expiry", skip_all, fields(key = %key))]
+    async fn get_expiry(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<u64>, Box<dyn std::error::Error + Send + Sync>> {
+        // Negative TTLs mark keys without an expiry or not held
+        let ttl: i64 = self.connection.ttl(key).await?;
+        if ttl < 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(Utc::now().timestamp() as u64 + ttl as u64))
+    }
+
+    #[instrument(level = "debug", name = "RedisCacheConn::get_keys", skip_all)]
+    async fn get_keys(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut keys = Vec::new();
+        let mut iter: redis::AsyncIter<String> = self.connection.scan().await?;
+
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+
+        Ok(keys)
+    }
+
+    /// Estimates the totals from a random sample of keys, so that the cost does not
+    /// grow with the size of the cache. The counts are exact while the cache holds
+    /// no more keys than the sample size
+    #[instrument(level = "debug", name = "RedisCacheConn::get_stats", skip_all)]
+    async fn get_stats(&mut self) -> Result<StoreStats, Box<dyn std::error::Error + Send + Sync>> {
+        let key_count: u64 = redis::cmd("DBSIZE")
+            .query_async(&mut self.connection)
+            .await?;
+        let exact = key_count <= REDIS_STATS_SAMPLE_SIZE as u64;
+
+        let sample: Vec<String> = if exact {
+            self.get_keys().await?
+        } else {
+            let mut pipe = redis::pipe();
+            for _ in 0..REDIS_STATS_SAMPLE_SIZE {
+                pipe.cmd("RANDOMKEY");
+            }
+            let keys: Vec<Option<String>> = pipe.query_async(&mut self.connection).await?;
+            keys.into_iter().flatten().collect()
+        };
+
+        if sample.is_empty() {
+            return Ok(StoreStats::default());
+        }
+
+        // Only string values can hold address data
+        let mut pipe = redis::pipe();
+        for key in &sample {
+            pipe.cmd("TYPE").arg(key);
+        }
+        let types: Vec<String> = pipe.query_async(&mut self.connection).await?;
+        let candidates: Vec<&String> = sample
+            .iter()
+            .zip(types)
+            .filter(|(key, kind)| kind == "string" && !is_internal_key(key))
+            .map(|(key, _)| key)
+            .collect();
+
+        let mut sampled = StoreStats::default();
+        if !candidates.is_empty() {
+            let mut pipe = redis::pipe();
+            for key in &candidates {
+                pipe.cmd("GET").arg(*key);
+            }
+            let values: Vec<Option<String>> = pipe.query_async(&mut self.connection).await?;
+
+            for (key, value) in candidates.iter().zip(values) {
+                let Some(value) = value else { continue };
+                let Ok(mapping) =
+                    serde_json::from_str::<HashMap<String, serde_json::Value>>(&value)
+                else {
+                    continue;
+                };
+
+                sampled.addresses += 1;
+                sampled.entries += mapping.len() as u64;
+                sampled.bytes += (key.len() + value.len()) as u64;
+            }
+        }
+
+        if exact {
+            return Ok(sampled);
+        }
+
+        // Scale the sample up to the whole keyspace
+        let scale = key_count as f64 / sample.len() as f64;
+        let estimate = |n: u64| (n as f64 * scale).round() as u64;
+        Ok(StoreStats {
+            addresses: estimate(sampled.addresses),
+            entries: estimate(sampled.entries),
+            bytes: estimate(sampled.bytes),
+        })
+    }
+    #[instrument(level = "debug", name = "RedisCacheConn::ping", skip_all)]
+    async fn ping(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let _: String = redis::cmd("PING").query_async(&mut self.connection).await?;
+        Ok(())
+    }
+
+    /// Only I/O errors, dropped connections and timeouts mean Redis cannot be
+    /// reached. Errors answered by Redis itself, e.g. for a bad command, do not
+    fn is_unreachable(error: &(dyn std::error::Error + 'static)) -> bool {
+        error
+            .downcast_ref::<redis::RedisError>()
+            .is_some_and(|e| e.is_io_error() || e.is_connection_dropped() || e.is_timeout())
+    }
 }
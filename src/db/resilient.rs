@@ -0,0 +1,407 @@
+use crate::constants::{CACHE_UNAVAILABLE, CONNECT_TIMEOUT, LOCAL_FALLBACK_PRUNE_THRESHOLD};
+use crate::db::handler::{CacheHandler, KvStoreConnection};
+use crate::interfaces::{RateLimit, StoreStats};
+use crate::metrics::record_cache_degraded;
+use crate::utils::connect_store;
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::lock::Mutex;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Cache which the node can run without. While the underlying cache cannot be
+/// reached, its operations fail straight away so that requests are served from
+/// the DB, and claims and rate limits are kept in memory for this node alone.
+/// Replay protection and rate limits then only hold per node, and the readiness
+/// probe reports the node as degraded.
+///
+/// Keys whose cached copies could not be updated are remembered and dropped
+/// from the cache before it is used again, so that it serves no stale data
+#[derive(Clone)]
+pub struct ResilientCache<T> {
+    url: String,
+    inner: Option<T>,
+    available: bool,
+    stale_keys: HashSet<String>,
+    local: LocalFallback,
+}
+
+impl<T: KvStoreConnection> ResilientCache<T> {
+    /// Wraps a cache connection, or the lack of one if the cache could not be
+    /// reached, in which case the node starts degraded
+    ///
+    /// ### Arguments
+    ///
+    /// * `url` - The URL to reconnect to
+    /// * `inner` - The cache connection, if one was made
+    pub fn new(url: &str, inner: Option<T>) -> Self {
+        let cache = ResilientCache {
+            url: url.to_string(),
+            available: inner.is_some(),
+            inner,
+            stale_keys: HashSet::new(),
+            local: LocalFallback::default(),
+        };
+        record_cache_degraded(!cache.available);
+        cache
+    }
+
+    /// Whether the cache is unavailable and requests are served without it
+    pub fn is_degraded(&self) -> bool {
+        !self.available
+    }
+
+    /// Connection to the cache, if it is available
+    fn connection(&mut self) -> Result<&mut T, Box<dyn std::error::Error + Send + Sync>> {
+        match self.inner.as_mut() {
+            Some(conn) if self.available => Ok(conn),
+            _ => Err(CACHE_UNAVAILABLE.into()),
+        }
+    }
+
+    /// Marks the cache unavailable if an operation on it failed because it could not
+    /// be reached, passing the result through. It stays unavailable until
+    /// `restore_cache` reaches it again
+    ///
+    /// ### Arguments
+    ///
+    /// * `result` - Result of the operation
+    fn observe<R>(
+        &mut self,
+        result: Result<R, Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Result<R, Box<dyn std::error::Error + Send + Sync>> {
+        if let Err(e) = &result {
+            if self.available && T::is_unreachable(e.as_ref()) {
+                warn!(
+                    "Cache unavailable, serving from the DB until it recovers: {}",
+                    e
+                );
+                self.available = false;
+                record_cache_degraded(true);
+            }
+        }
+        result
+    }
+
+    /// Passes through the result of a write, remembering the key if its cached
+    /// copy may now be out of date
+    ///
+    /// ### Arguments
+    ///
+    /// * `key` - Key written to
+    /// * `result` - Result of the write
+    fn observe_write(
+        &mut self,
+        key: &str,
+        result: Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if result.is_err() {
+            self.stale_keys.insert(key.to_string());
+        }
+        self.observe(result)
+    }
+}
+
+/// Reaches the cache again if it is unavailable. Cached copies of keys written
+/// during the outage are dropped and claims made in memory are carried over
+/// before the cache is used again
+///
+/// ### Arguments
+///
+/// * `cache` - The cache to restore
+pub async fn restore_cache<T: KvStoreConnection + CacheHandler + Clone + Send>(
+    cache: &Arc<Mutex<ResilientCache<T>>>,
+) -> Result<(), String> {
+    let (url, conn) = {
+        let cache = cache.lock().await;
+        if !cache.is_degraded() {
+            return Ok(());
+        }
+        (cache.url.clone(), cache.inner.clone())
+    };
+
+    // Reached outside the lock, so that requests are not held up while the cache is down
+    let mut conn = match conn {
+        Some(conn) => conn,
+        None => connect_store::<T>(&url).await?,
+    };
+    match tokio::time::timeout(Duration::from_millis(CONNECT_TIMEOUT), conn.ping()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => return Err(format!("Cache still unavailable: {}", e)),
+        Err(_) => {
+            return Err(format!(
+                "Cache still unavailable: no answer within {}ms",
+                CONNECT_TIMEOUT
+            ))
+        }
+    }
+
+    let mut cache = cache.lock().await;
+    for key in cache.stale_keys.iter() {
+        if let Err(e) = conn.del_data(key, None).await {
+            return Err(format!(
+                "Failed to drop stale cached copy of {}: {}",
+                key, e
+            ));
+        }
+    }
+
+    let now = Utc::now().timestamp_millis();
+    for (key, until) in cache.local.claims.iter().filter(|(_, until)| **until > now) {
+        let seconds = ((until - now) as u64).div_ceil(1000) as usize;
+        if let Err(e) = conn.claim_key(key, seconds).await {
+            return Err(format!("Failed to carry over claim {}: {}", key, e));
+        }
+    }
+
+    info!(
+        "Cache available again, dropped {} stale cached copies",
+        cache.stale_keys.len()
+    );
+    cache.stale_keys.clear();
+    cache.local = LocalFallback::default();
+    cache.inner = Some(conn);
+    cache.available = true;
+    record_cache_degraded(false);
+    Ok(())
+}
+
+#[async_trait]
+impl<T: KvStoreConnection + CacheHandler + Send> CacheHandler for ResilientCache<T> {
+    async fn expire_entry(
+        &mut self,
+        key: &str,
+        seconds: usize,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let result = match self.connection() {
+            Ok(conn) => conn.expire_entry(key, seconds).await,
+            Err(e) => Err(e),
+        };
+        self.observe_write(key, result)
+    }
+
+    async fn claim_key(
+        &mut self,
+        key: &str,
+        seconds: usize,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let result = match self.connection() {
+            Ok(conn) => conn.claim_key(key, seconds).await,
+            Err(e) => Err(e),
+        };
+        match self.observe(result) {
+            Ok(claimed) => Ok(claimed),
+            Err(_) if self.is_degraded() => Ok(self.local.claim_key(key, seconds)),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn take_token(
+        &mut self,
+        key: &str,
+        limit: RateLimit,
+    ) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let result = match self.connection() {
+            Ok(conn) => conn.take_token(key, limit).await,
+            Err(e) => Err(e),
+        };
+        match self.observe(result) {
+            Ok(wait) => Ok(wait),
+            Err(_) if self.is_degraded() => Ok(self.local.take_token(key, limit)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: KvStoreConnection + Send> KvStoreConnection for ResilientCache<T> {
+    const BACKEND: &'static str = T::BACKEND;
+
+    async fn init(url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(ResilientCache::new(url, Some(T::init(url).await?)))
+    }
+
+    async fn set_data<V: Serialize + Send + DeserializeOwned>(
+        &mut self,
+        key: &str,
+        value_id: &str,
+        value: V,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let result = match self.connection() {
+            Ok(conn) => conn.set_data(key, value_id, value).await,
+            Err(e) => Err(e),
+        };
+        self.observe_write(key, result)
+    }
+
+    async fn set_data_with_expiry<V: Serialize + DeserializeOwned + Send>(
+        &mut self,
+        key: &str,
+        value_id: &str,
+        value: V,
+        seconds: usize,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let result = match self.connection() {
+            Ok(conn) => {
+                conn.set_data_with_expiry(key, value_id, value, seconds)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        self.observe_write(key, result)
+    }
+
+    async fn del_data(
+        &mut self,
+        key: &str,
+        value_id: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let result = match self.connection() {
+            Ok(conn) => conn.del_data(key, value_id).await,
+            Err(e) => Err(e),
+        };
+        self.observe_write(key, result)
+    }
+
+    async fn get_data<V: Clone + DeserializeOwned>(
+        &mut self,
+        key: &str,
+        value_id: Option<&str>,
+    ) -> Result<Option<HashMap<String, V>>, Box<dyn std::error::Error + Send + Sync>> {
+        let result = match self.connection() {
+            Ok(conn) => conn.get_data(key, value_id).await,
+            Err(e) => Err(e),
+        };
+        self.observe(result)
+    }
+
+    async fn get_expiry(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<u64>, Box<dyn std::error::Error + Send + Sync>> {
+        let result = match self.connection() {
+            Ok(conn) => conn.get_expiry(key).await,
+            Err(e) => Err(e),
+        };
+        self.observe(result)
+    }
+
+    async fn get_keys(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let result = match self.connection() {
+            Ok(conn) => conn.get_keys().await,
+            Err(e) => Err(e),
+        };
+        self.observe(result)
+    }
+
+    async fn get_stats(&mut self) -> Result<StoreStats, Box<dyn std::error::Error + Send + Sync>> {
+        let result = match self.connection() {
+            Ok(conn) => conn.get_stats().await,
+            Err(e) => Err(e),
+        };
+        self.observe(result)
+    }
+
+    async fn ping(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let result = match self.connection() {
+            Ok(conn) => conn.ping().await,
+            Err(e) => Err(e),
+        };
+        self.observe(result)
+    }
+
+    fn is_unreachable(error: &(dyn std::error::Error + 'static)) -> bool {
+        T::is_unreachable(error)
+    }
+}
+
+// ========== LOCAL FALLBACK ========== //
+
+/// Claims and rate limiting buckets kept in memory while the cache is unavailable
+#[derive(Debug, Clone, Default)]
+struct LocalFallback {
+    /// Claimed keys, with the Unix time in milliseconds their claim lapses
+    claims: HashMap<String, i64>,
+    buckets: HashMap<String, LocalBucket>,
+}
+
+/// Rate limiting bucket, refilled the same way as the cache's buckets
+#[derive(Debug, Clone, Copy)]
+struct LocalBucket {
+    tokens: f64,
+    /// Unix time in milliseconds the bucket was last refilled
+    refilled_at: i64,
+    /// Unix time in milliseconds the bucket is full again if left unused
+    full_at: i64,
+}
+
+impl LocalFallback {
+    /// Claims a key unless it is already claimed. Returns whether it was newly claimed
+    ///
+    /// ### Arguments
+    ///
+    /// * `key` - Key to claim
+    /// * `seconds` - Number of seconds to hold the claim for
+    fn claim_key(&mut self, key: &str, seconds: usize) -> bool {
+        let now = Utc::now().timestamp_millis();
+        self.prune(now);
+
+        if self.claims.get(key).is_some_and(|until| *until > now) {
+            return false;
+        }
+        self.claims
+            .insert(key.to_string(), now + seconds as i64 * 1000);
+        true
+    }
+
+    /// Takes a token from a bucket. Returns the milliseconds until a token is
+    /// available, or zero if one was taken
+    ///
+    /// ### Arguments
+    ///
+    /// * `key` - Key of the bucket
+    /// * `limit` - Capacity and refill rate of the bucket
+    fn take_token(&mut self, key: &str, limit: RateLimit) -> u64 {
+        let now = Utc::now().timestamp_millis();
+        self.prune(now);
+
+        let capacity = limit.capacity as f64;
+        let bucket = self.buckets.entry(key.to_string()).or_insert(LocalBucket {
+            tokens: capacity,
+            refilled_at: now,
+            full_at: now,
+        });
+
+        let elapsed = (now - bucket.refilled_at).max(0) as f64;
+        let mut tokens = capacity.min(bucket.tokens + elapsed * limit.refill_per_second / 1000.0);
+        let wait = if tokens >= 1.0 {
+            tokens -= 1.0;
+            0
+        } else {
+            ((1.0 - tokens) * 1000.0 / limit.refill_per_second).ceil() as u64
+        };
+
+        *bucket = LocalBucket {
+            tokens,
+            refilled_at: now,
+            full_at: now + (capacity * 1000.0 / limit.refill_per_second).ceil() as i64,
+        };
+        wait
+    }
+
+    /// Drops lapsed claims and full buckets once enough are held
+    ///
+    /// ### Arguments
+    ///
+    /// * `now` - Unix time in milliseconds
+    fn prune(&mut self, now: i64) {
+        if self.claims.len() + self.buckets.len() < LOCAL_FALLBACK_PRUNE_THRESHOLD {
+            return;
+        }
+        self.claims.retain(|_, until| *until > now);
+        self.buckets.retain(|_, bucket| bucket.full_at > now);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::blocklist::BlockEntry;
+use crate::constants::{ALLOWLIST_KEY_PREFIX, BLOCKLIST_KEY, SCHEMA_VERSION_FIELD};
+use crate::db::handler::KvStoreConnection;
+use crate::interfaces::{AllowEntry, SetSaveData};
+use crate::utils::is_internal_key;
+use chrono::Utc;
+use futures::lock::Mutex;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::info;
+
+// ========== SCHEMAS ========== //
+
+/// Upgrades the fields of a stored record from the shape one schema version earlier
+pub type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+/// Brings a stored record of some schema up to its current shape, as `upgrade_record` does
+type Upgrade = fn(&mut Value) -> Result<bool, String>;
+
+/// A record shape kept in the stores. Records are stored with the version of
+/// their shape, so that the shape can change without breaking stored data
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// Name of the shape, used in errors
+    const SCHEMA: &'static str;
+
+    /// Migrations in order, the one at index `i` upgrading version `i` to `i + 1`
+    const MIGRATIONS: &'static [Migration];
+
+    /// Version of the current shape
+    fn schema_version() -> u32 {
+        Self::MIGRATIONS.len() as u32
+    }
+}
+
+/// Records written before versioning already have the version 1 shape, so
+/// only need stamping with it
+fn stamp_unversioned(_record: &mut Map<String, Value>) -> Result<(), String> {
+    Ok(())
+}
+
+/// Entries gained the time they were saved in version 2. Older entries are
+/// dated to their migration, so that retention counts from then
+fn date_entry(record: &mut Map<String, Value>) -> Result<(), String> {
+    record
+        .entry("saved_at")
+        .or_insert(json!(Utc::now().timestamp().max(0)));
+    Ok(())
+}
+
+impl Versioned for SetSaveData {
+    const SCHEMA: &'static str = "entry";
+    const MIGRATIONS: &'static [Migration] = &[stamp_unversioned, date_entry];
+}
+
+impl Versioned for AllowEntry {
+    const SCHEMA: &'static str = "allowlist entry";
+    const MIGRATIONS: &'static [Migration] = &[stamp_unversioned];
+}
+
+impl Versioned for BlockEntry {
+    const SCHEMA: &'static str = "blocklist entry";
+    const MIGRATIONS: &'static [Migration] = &[stamp_unversioned];
+}
+
+// ========== MIGRATION ========== //
+
+/// Schema version a stored record was written with
+///
+/// ### Arguments
+///
+/// * `record` - Record as stored
+pub fn record_version(record: &Value) -> u32 {
+    record
+        .get(SCHEMA_VERSION_FIELD)
+        .and_then(Value::as_u64)
+        .map_or(0, |version| version as u32)
+}
+
+/// Brings a stored record up to the current shape of its schema, returning
+/// whether it was changed. Records from a newer node are refused rather than
+/// read in a shape this node does not know
+///
+/// ### Arguments
+///
+/// * `record` - Record as stored
+pub fn upgrade_record<T: Versioned>(record: &mut Value) -> Result<bool, String> {
+    let version = record_version(record);
+    let current = T::schema_version();
+    if version > current {
+        return Err(format!(
+            "Stored {} has schema version {}, newer than the supported {}",
+            T::SCHEMA,
+            version,
+            current
+        ));
+    }
+    if version == current {
+        return Ok(false);
+    }
+
+    let fields = record
+        .as_object_mut()
+        .ok_or(format!("Stored {} is not an object", T::SCHEMA))?;
+    for (from, migration) in T::MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(fields).map_err(|e| {
+            format!(
+                "Failed to migrate {} from schema version {} with error: {}",
+                T::SCHEMA,
+                from,
+                e
+            )
+        })?;
+    }
+    fields.insert(SCHEMA_VERSION_FIELD.to_string(), json!(current));
+
+    Ok(true)
+}
+
+/// Reads a stored record, upgrading it first if it is in an older shape
+///
+/// ### Arguments
+///
+/// * `record` - Record as stored
+pub fn decode_record<T: Versioned>(mut record: Value) -> Result<T, String> {
+    upgrade_record::<T>(&mut record)?;
+    serde_json::from_value(record)
+        .map_err(|e| format!("Failed to read stored {} with error: {}", T::SCHEMA, e))
+}
+
+/// Prepares a record to be stored, stamped with the current version of its schema
+///
+/// ### Arguments
+///
+/// * `record` - Record to store
+pub fn encode_record<T: Versioned>(record: &T) -> Value {
+    let mut value = json!(record);
+    if let Some(fields) = value.as_object_mut() {
+        fields.insert(SCHEMA_VERSION_FIELD.to_string(), json!(T::schema_version()));
+    }
+    value
+}
+
+// ========== STORE ACCESS ========== //
+
+/// Gets records from a store, upgrading any in older shapes as they are read.
+/// The stored copies are upgraded when they are next written
+///
+/// ### Arguments
+///
+/// * `store` - The store to read from
+/// * `key` - Key of the records
+/// * `value_id` - ID of the record to get. If not provided, all records for the key are retrieved
+pub async fn get_records<T: Versioned, S: KvStoreConnection>(
+    store: &mut S,
+    key: &str,
+    value_id: Option<&str>,
+) -> Result<Option<HashMap<String, T>>, Box<dyn std::error::Error + Send + Sync>> {
+    let records = match store.get_data::<Value>(key, value_id).await? {
+        Some(records) => records,
+        None => return Ok(None),
+    };
+
+    let mut decoded = HashMap::with_capacity(records.len());
+    for (id, record) in records {
+        decoded.insert(id, decode_record(record)?);
+    }
+    Ok(Some(decoded))
+}
+
+/// Sets a record in a store, stamped with the current version of its schema
+///
+/// ### Arguments
+///
+/// * `store` - The store to write to
+/// * `key` - Key of the record
+/// * `value_id` - ID of the record
+/// * `record` - Record to set
+pub async fn set_record<T: Versioned, S: KvStoreConnection>(
+    store: &mut S,
+    key: &str,
+    value_id: &str,
+    record: &T,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    store.set_data(key, value_id, encode_record(record)).await
+}
+
+/// Upgrade for the records held under a key, or `None` for keys holding node
+/// state whose format is versioned separately
+///
+/// ### Arguments
+///
+/// * `key` - Key holding the records
+fn record_upgrade(key: &str) -> Option<Upgrade> {
+    if key == BLOCKLIST_KEY {
+        Some(upgrade_record::<BlockEntry>)
+    } else if key.starts_with(ALLOWLIST_KEY_PREFIX) {
+        Some(upgrade_record::<AllowEntry>)
+    } else if !is_internal_key(key) {
+        Some(upgrade_record::<SetSaveData>)
+    } else {
+        None
+    }
+}
+
+/// Upgrades every record in the DB stored in an older shape, returning how many
+/// were rewritten. Each key is upgraded under the DB lock, so concurrent writes
+/// are not lost
+///
+/// ### Arguments
+///
+/// * `db` - The database connection
+pub async fn migrate_records<T: KvStoreConnection>(db: Arc<Mutex<T>>) -> Result<usize, String> {
+    let keys = db
+        .lock()
+        .await
+        .get_keys()
+        .await
+        .map_err(|e| format!("Failed to list keys in DB with error: {}", e))?;
+
+    let mut migrated = 0;
+    let mut errors = Vec::new();
+
+    for key in keys {
+        let Some(upgrade) = record_upgrade(&key) else {
+            continue;
+        };
+
+        let mut db_lock = db.lock().await;
+        let records = match db_lock.get_data::<Value>(&key, None).await {
+            Ok(records) => records.unwrap_or_default(),
+            Err(e) => {
+                errors.push(format!("{}: {}", key, e));
+                continue;
+            }
+        };
+
+        for (value_id, mut record) in records {
+            match upgrade(&mut record) {
+                Ok(false) => continue,
+                Ok(true) => {}
+                Err(e) => {
+                    errors.push(format!("{}/{}: {}", key, value_id, e));
+                    continue;
+                }
+            }
+
+            match db_lock.set_data(&key, &value_id, record).await {
+                Ok(_) => migrated += 1,
+                Err(e) => errors.push(format!("{}/{}: {}", key, value_id, e)),
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(format!(
+            "Failed to migrate {} records with error: {}",
+            errors.len(),
+            errors.join(", ")
+        ));
+    }
+
+    info!(
+        "Migrated {} stored records to their current schema",
+        migrated
+    );
+    Ok(migrated)
+}
@@ -0,0 +1,320 @@
+use ring::aead::{Aad, LessSafeKey, Nonce as AeadNonce, UnboundKey, CHACHA20_POLY1305};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use valence_core::crypto::secretbox_chacha20_poly1305::{
+    gen_key, gen_nonce, open, seal, Key, Nonce, KEY_LEN,
+};
+
+use crate::constants::ENVELOPE_VERSION;
+
+/// Contents of a keyfile: hex-encoded key encryption keys by ID, and which
+/// of them new data is encrypted under
+#[derive(Deserialize)]
+struct Keyfile {
+    active: String,
+    keys: HashMap<String, String>,
+}
+
+/// Envelope stored in place of an entry's data when encryption at rest is enabled.
+///
+/// The data is sealed with a fresh data key, which is in turn sealed with a key
+/// from the keyfile. Rotating keys therefore only re-seals the data key. The
+/// address and value ID the entry is stored under are bound to the sealed data
+/// as associated data, so an envelope moved to another entry fails to open
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EncryptedData {
+    /// Marks the value as an envelope rather than plaintext data
+    #[serde(rename = "_valence_envelope")]
+    pub version: u8,
+    /// ID of the keyfile key sealing the data key
+    pub key_id: String,
+    /// Hex-encoded sealed data key
+    pub wrapped_key: String,
+    /// Hex-encoded nonce used to seal the data key
+    pub key_nonce: String,
+    /// Hex-encoded sealed data
+    pub ciphertext: String,
+    /// Hex-encoded nonce used to seal the data
+    pub nonce: String,
+}
+
+impl EncryptedData {
+    /// Reads an envelope from a stored value, if it is one
+    ///
+    /// ### Arguments
+    ///
+    /// * `data` - Value as stored
+    pub fn from_value(data: &Value) -> Option<Self> {
+        match data {
+            Value::Object(_) => serde_json::from_value(data.clone()).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Key encryption keys loaded from a local keyfile
+pub struct Keyring {
+    active: String,
+    keys: HashMap<String, Key>,
+}
+
+impl Keyring {
+    /// Loads a keyring from a JSON keyfile of the form
+    /// `{ "active": "<id>", "keys": { "<id>": "<hex key>" } }`
+    ///
+    /// ### Arguments
+    ///
+    /// * `path` - Path of the keyfile
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read keyfile {path} with error: {e}"))?;
+        Self::from_json(&contents)
+    }
+
+    /// Parses a keyring from the contents of a keyfile
+    ///
+    /// ### Arguments
+    ///
+    /// * `contents` - JSON contents of the keyfile
+    pub fn from_json(contents: &str) -> Result<Self, String> {
+        let keyfile: Keyfile = serde_json::from_str(contents)
+            .map_err(|e| format!("Failed to parse keyfile with error: {e}"))?;
+
+        let mut keys = HashMap::new();
+        for (id, key) in keyfile.keys {
+            let key = hex::decode(&key)
+                .ok()
+                .and_then(|bytes| Key::from_slice(&bytes))
+                .ok_or(format!(
+                    "Key {id} in keyfile must be {KEY_LEN} hex-encoded bytes"
+                ))?;
+            keys.insert(id, key);
+        }
+
+        if !keys.contains_key(&keyfile.active) {
+            return Err(format!(
+                "Active key {} is missing from keyfile",
+                keyfile.active
+            ));
+        }
+
+        Ok(Keyring {
+            active: keyfile.active,
+            keys,
+        })
+    }
+
+    /// ID of the key new data is encrypted under
+    pub fn active_key_id(&self) -> &str {
+        &self.active
+    }
+
+    /// Looks up a key by ID
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the key
+    fn key(&self, id: &str) -> Result<&Key, String> {
+        self.keys
+            .get(id)
+            .ok_or(format!("Key {id} is missing from keyfile"))
+    }
+
+    /// Seals a data key under the active key
+    ///
+    /// ### Arguments
+    ///
+    /// * `data_key` - Data key to seal
+    fn wrap_key(&self, data_key: &Key) -> Result<(String, String), String> {
+        let key_nonce = gen_nonce();
+        let wrapped = seal(
+            data_key.as_ref().to_vec(),
+            &key_nonce,
+            self.key(&self.active)?,
+        )
+        .ok_or("Failed to seal data key")?;
+        Ok((hex::encode(wrapped), hex::encode(key_nonce)))
+    }
+
+    /// Opens the data key of an envelope
+    ///
+    /// ### Arguments
+    ///
+    /// * `envelope` - Envelope holding the sealed data key
+    fn unwrap_key(&self, envelope: &EncryptedData) -> Result<Key, String> {
+        let wrapped = decode_hex(&envelope.wrapped_key)?;
+        let key_nonce = decode_nonce(&envelope.key_nonce)?;
+        open(wrapped, &key_nonce, self.key(&envelope.key_id)?)
+            .and_then(|bytes| Key::from_slice(&bytes))
+            .ok_or(format!(
+                "Failed to open data key sealed by key {}",
+                envelope.key_id
+            ))
+    }
+
+    /// Encrypts data under a fresh data key, sealed with the active key
+    ///
+    /// ### Arguments
+    ///
+    /// * `data` - Plaintext data
+    /// * `address` - Address the entry is stored under
+    /// * `data_id` - Value ID the entry is stored under
+    pub fn encrypt(&self, data: &Value, address: &str, data_id: &str) -> Result<Value, String> {
+        let plaintext = serde_json::to_vec(data).map_err(|e| e.to_string())?;
+        let data_key = gen_key();
+        let nonce = gen_nonce();
+        let ciphertext = seal_bound(
+            plaintext,
+            &nonce,
+            &data_key,
+            &associated_data(address, data_id),
+        )
+        .ok_or("Failed to seal data")?;
+        let (wrapped_key, key_nonce) = self.wrap_key(&data_key)?;
+
+        serde_json::to_value(EncryptedData {
+            version: ENVELOPE_VERSION,
+            key_id: self.active.clone(),
+            wrapped_key,
+            key_nonce,
+            ciphertext: hex::encode(ciphertext),
+            nonce: hex::encode(nonce),
+        })
+        .map_err(|e| e.to_string())
+    }
+
+    /// Decrypts stored data. Data which is not an envelope, e.g. saved before
+    /// encryption was enabled, is returned as is
+    ///
+    /// ### Arguments
+    ///
+    /// * `data` - Data as stored
+    /// * `address` - Address the entry is stored under
+    /// * `data_id` - Value ID the entry is stored under
+    pub fn decrypt(&self, data: Value, address: &str, data_id: &str) -> Result<Value, String> {
+        let envelope = match EncryptedData::from_value(&data) {
+            Some(envelope) => envelope,
+            None => return Ok(data),
+        };
+
+        let data_key = self.unwrap_key(&envelope)?;
+        let ciphertext = decode_hex(&envelope.ciphertext)?;
+        let nonce = decode_nonce(&envelope.nonce)?;
+        let plaintext = open_bound(
+            ciphertext,
+            &nonce,
+            &data_key,
+            &associated_data(address, data_id),
+        )
+        .ok_or("Failed to open data")?;
+
+        serde_json::from_slice(&plaintext).map_err(|e| e.to_string())
+    }
+
+    /// Brings stored data under the active key, returning `None` if it already is.
+    /// Envelopes under other keys only have their data key re-sealed, while
+    /// plaintext data is encrypted
+    ///
+    /// ### Arguments
+    ///
+    /// * `data` - Data as stored
+    /// * `address` - Address the entry is stored under
+    /// * `data_id` - Value ID the entry is stored under
+    pub fn reencrypt(
+        &self,
+        data: &Value,
+        address: &str,
+        data_id: &str,
+    ) -> Result<Option<Value>, String> {
+        let mut envelope = match EncryptedData::from_value(data) {
+            Some(envelope) => envelope,
+            None => return self.encrypt(data, address, data_id).map(Some),
+        };
+
+        if envelope.key_id == self.active {
+            return Ok(None);
+        }
+
+        let data_key = self.unwrap_key(&envelope)?;
+        (envelope.wrapped_key, envelope.key_nonce) = self.wrap_key(&data_key)?;
+        envelope.key_id = self.active.clone();
+
+        serde_json::to_value(envelope)
+            .map(Some)
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut ids: Vec<_> = self.keys.keys().collect();
+        ids.sort();
+        write!(f, "Keyring(active: {}, keys: {:?})", self.active, ids)
+    }
+}
+
+/// Constructs the associated data binding sealed data to the entry it is stored under
+///
+/// ### Arguments
+///
+/// * `address` - Address the entry is stored under
+/// * `data_id` - Value ID the entry is stored under
+fn associated_data(address: &str, data_id: &str) -> Vec<u8> {
+    format!("{}:{}:{}", address.len(), address, data_id).into_bytes()
+}
+
+/// Seals data with associated data, which must be given again to open it
+///
+/// ### Arguments
+///
+/// * `plaintext` - Data to seal
+/// * `nonce` - Single-use nonce
+/// * `key` - Key to seal with
+/// * `aad` - Associated data
+fn seal_bound(mut plaintext: Vec<u8>, nonce: &Nonce, key: &Key, aad: &[u8]) -> Option<Vec<u8>> {
+    let key = LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, key.as_ref()).ok()?);
+    let nonce = AeadNonce::try_assume_unique_for_key(nonce.as_ref()).ok()?;
+    key.seal_in_place_append_tag(nonce, Aad::from(aad), &mut plaintext)
+        .ok()?;
+    Some(plaintext)
+}
+
+/// Opens data sealed with associated data
+///
+/// ### Arguments
+///
+/// * `ciphertext` - Sealed data
+/// * `nonce` - Nonce the data was sealed with
+/// * `key` - Key the data was sealed with
+/// * `aad` - Associated data the data was sealed with
+fn open_bound(mut ciphertext: Vec<u8>, nonce: &Nonce, key: &Key, aad: &[u8]) -> Option<Vec<u8>> {
+    let key = LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, key.as_ref()).ok()?);
+    let nonce = AeadNonce::try_assume_unique_for_key(nonce.as_ref()).ok()?;
+    let len = key
+        .open_in_place(nonce, Aad::from(aad), &mut ciphertext)
+        .ok()?
+        .len();
+    ciphertext.truncate(len);
+    Some(ciphertext)
+}
+
+/// Decodes a hex field of an envelope
+///
+/// ### Arguments
+///
+/// * `field` - Hex-encoded field
+fn decode_hex(field: &str) -> Result<Vec<u8>, String> {
+    hex::decode(field).map_err(|_| "Envelope field is not valid hex".to_string())
+}
+
+/// Decodes a hex nonce field of an envelope
+///
+/// ### Arguments
+///
+/// * `field` - Hex-encoded nonce
+fn decode_nonce(field: &str) -> Result<Nonce, String> {
+    Nonce::from_slice(&decode_hex(field)?).ok_or("Envelope nonce has the wrong length".to_string())
+}
@@ -0,0 +1,137 @@
+use crate::constants::{BLOOM_FILTER_CAPACITY, BLOOM_FILTER_FP_RATE, FILTER_SECRET_ID_LEN};
+use crate::filter::handler::{FilterKind, MembershipFilter, StorageReadyFilter};
+use crate::filter::hasher::StableHasher;
+use crate::filter::storage::HasherId;
+use std::hash::Hasher;
+
+/// Compact probabilistic filter. Bloom filters cannot remove items, so an
+/// address stays a (false) positive after its data is deleted
+pub struct BloomMembershipFilter {
+    bits: Vec<u8>,
+    num_hashes: u8,
+    length: usize,
+}
+
+impl BloomMembershipFilter {
+    pub fn new() -> Self {
+        Self::with_capacity(BLOOM_FILTER_CAPACITY, BLOOM_FILTER_FP_RATE)
+    }
+
+    /// Constructs a filter sized for the given capacity and false positive rate
+    ///
+    /// ### Arguments
+    ///
+    /// * `capacity` - Expected number of items
+    /// * `fp_rate` - Target false positive rate at capacity
+    pub fn with_capacity(capacity: usize, fp_rate: f64) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-(capacity as f64) * fp_rate.ln() / (ln2 * ln2)).ceil() as usize;
+        let num_hashes = ((num_bits as f64 / capacity as f64) * ln2).round() as u8;
+
+        BloomMembershipFilter {
+            bits: vec![0; num_bits.div_ceil(8).max(1)],
+            num_hashes: num_hashes.max(1),
+            length: 0,
+        }
+    }
+
+    /// Bit indexes for an item, using double hashing
+    fn indexes(&self, item: &str) -> Vec<usize> {
+        let mut hasher = StableHasher::default();
+        hasher.write(item.as_bytes());
+        let h1 = hasher.finish();
+        hasher.write(&h1.to_le_bytes());
+        let h2 = hasher.finish();
+
+        let num_bits = (self.bits.len() * 8) as u64;
+        (0..self.num_hashes as u64)
+            .map(|i| (h1.wrapping_add(i.wrapping_mul(h2)) % num_bits) as usize)
+            .collect()
+    }
+}
+
+impl Default for BloomMembershipFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MembershipFilter for BloomMembershipFilter {
+    fn add(&mut self, item: &str) -> Result<(), String> {
+        if self.contains(item) {
+            return Ok(());
+        }
+
+        for idx in self.indexes(item) {
+            self.bits[idx / 8] |= 1 << (idx % 8);
+        }
+        self.length += 1;
+        Ok(())
+    }
+
+    fn contains(&self, item: &str) -> bool {
+        self.indexes(item)
+            .into_iter()
+            .all(|idx| self.bits[idx / 8] & (1 << (idx % 8)) != 0)
+    }
+
+    /// Items cannot be removed from a bloom filter, so this only reports membership
+    fn delete(&mut self, item: &str) -> bool {
+        self.contains(item)
+    }
+
+    fn len(&self) -> usize {
+        self.length
+    }
+
+    fn kind(&self) -> FilterKind {
+        FilterKind::Bloom
+    }
+
+    /// The number of items for which the filter's hash count is optimal
+    fn capacity(&self) -> Option<usize> {
+        let num_bits = (self.bits.len() * 8) as f64;
+        Some((num_bits * std::f64::consts::LN_2 / self.num_hashes as f64) as usize)
+    }
+
+    /// `(1 - e^(-kn/m))^k` for `k` hashes, `n` items and `m` bits
+    fn false_positive_rate(&self) -> f64 {
+        let k = self.num_hashes as f64;
+        let m = (self.bits.len() * 8) as f64;
+        (1.0 - (-k * self.length as f64 / m).exp()).powf(k)
+    }
+
+    /// The first exported byte holds the number of hash functions, followed by the bits
+    fn export(&self) -> StorageReadyFilter {
+        let mut values = Vec::with_capacity(self.bits.len() + 1);
+        values.push(self.num_hashes);
+        values.extend_from_slice(&self.bits);
+
+        StorageReadyFilter {
+            kind: FilterKind::Bloom,
+            hasher: HasherId::SipHash13,
+            key_id: [0; FILTER_SECRET_ID_LEN],
+            shard_index: 0,
+            shard_count: 1,
+            values,
+            length: self.length,
+        }
+    }
+}
+
+impl TryFrom<StorageReadyFilter> for BloomMembershipFilter {
+    type Error = String;
+
+    fn try_from(stored: StorageReadyFilter) -> Result<Self, Self::Error> {
+        match stored.values.split_first() {
+            Some((&num_hashes, bits)) if num_hashes > 0 && !bits.is_empty() => {
+                Ok(BloomMembershipFilter {
+                    bits: bits.to_vec(),
+                    num_hashes,
+                    length: stored.length,
+                })
+            }
+            _ => Err("Failed to import bloom filter: malformed export".to_string()),
+        }
+    }
+}
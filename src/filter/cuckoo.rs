@@ -0,0 +1,100 @@
+use crate::constants::{CUCKOO_BUCKET_SIZE, CUCKOO_FINGERPRINT_BITS, FILTER_SECRET_ID_LEN};
+use crate::filter::handler::{FilterKind, MembershipFilter, StorageReadyFilter};
+use crate::filter::hasher::{StableHasher, StableKey};
+use crate::filter::storage::HasherId;
+use cuckoofilter::{CuckooFilter, ExportedCuckooFilter};
+
+/// Compact probabilistic filter which supports deletion
+pub struct CuckooMembershipFilter {
+    filter: CuckooFilter<StableHasher>,
+    capacity: usize,
+}
+
+impl CuckooMembershipFilter {
+    pub fn new() -> Self {
+        Self::with_capacity(cuckoofilter::DEFAULT_CAPACITY)
+    }
+
+    /// Constructs a filter with room for at least the given number of items
+    ///
+    /// ### Arguments
+    ///
+    /// * `capacity` - Number of items to size the filter for
+    pub fn with_capacity(capacity: usize) -> Self {
+        let filter = CuckooFilter::with_capacity(capacity);
+        // One exported byte per fingerprint slot
+        let capacity = filter.export().values.len();
+
+        CuckooMembershipFilter { filter, capacity }
+    }
+}
+
+impl Default for CuckooMembershipFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MembershipFilter for CuckooMembershipFilter {
+    fn add(&mut self, item: &str) -> Result<(), String> {
+        self.filter
+            .add(&StableKey(item.as_bytes()))
+            .map_err(|e| e.to_string())
+    }
+
+    fn contains(&self, item: &str) -> bool {
+        self.filter.contains(&StableKey(item.as_bytes()))
+    }
+
+    fn delete(&mut self, item: &str) -> bool {
+        self.filter.delete(&StableKey(item.as_bytes()))
+    }
+
+    fn len(&self) -> usize {
+        self.filter.len()
+    }
+
+    fn kind(&self) -> FilterKind {
+        FilterKind::Cuckoo
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(self.capacity)
+    }
+
+    /// Upper bound of `2b / 2^f` scaled by the load factor, for `b` slots per
+    /// bucket and `f` bit fingerprints
+    fn false_positive_rate(&self) -> f64 {
+        let load = self.filter.len() as f64 / self.capacity.max(1) as f64;
+        let max_rate = 2.0 * CUCKOO_BUCKET_SIZE as f64 / 2f64.powi(CUCKOO_FINGERPRINT_BITS as i32);
+        max_rate * load
+    }
+
+    fn export(&self) -> StorageReadyFilter {
+        let cf = self.filter.export();
+        StorageReadyFilter {
+            kind: FilterKind::Cuckoo,
+            hasher: HasherId::SipHash13,
+            key_id: [0; FILTER_SECRET_ID_LEN],
+            shard_index: 0,
+            shard_count: 1,
+            values: cf.values,
+            length: cf.length,
+        }
+    }
+}
+
+impl From<StorageReadyFilter> for CuckooMembershipFilter {
+    fn from(stored: StorageReadyFilter) -> Self {
+        let capacity = stored.values.len();
+        let cfe = ExportedCuckooFilter {
+            values: stored.values,
+            length: stored.length,
+        };
+
+        CuckooMembershipFilter {
+            filter: CuckooFilter::from(cfe),
+            capacity,
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::constants::FILTER_SECRET_ID_LEN;
+use crate::filter::handler::{FilterKind, MembershipFilter, StorageReadyFilter};
+use crate::filter::storage::HasherId;
+use std::collections::HashSet;
+
+/// Filter holding every item in full. Never gives false positives, but its
+/// size grows with the number and length of stored addresses
+#[derive(Default)]
+pub struct ExactMembershipFilter {
+    items: HashSet<String>,
+}
+
+impl ExactMembershipFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MembershipFilter for ExactMembershipFilter {
+    fn add(&mut self, item: &str) -> Result<(), String> {
+        self.items.insert(item.to_string());
+        Ok(())
+    }
+
+    fn contains(&self, item: &str) -> bool {
+        self.items.contains(item)
+    }
+
+    fn delete(&mut self, item: &str) -> bool {
+        self.items.remove(item)
+    }
+
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    fn kind(&self) -> FilterKind {
+        FilterKind::Exact
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        None
+    }
+
+    fn false_positive_rate(&self) -> f64 {
+        0.0
+    }
+
+    fn export(&self) -> StorageReadyFilter {
+        let items: Vec<&String> = self.items.iter().collect();
+        StorageReadyFilter {
+            kind: FilterKind::Exact,
+            hasher: HasherId::SipHash13,
+            key_id: [0; FILTER_SECRET_ID_LEN],
+            shard_index: 0,
+            shard_count: 1,
+            values: serde_json::to_vec(&items).unwrap_or_default(),
+            length: self.items.len(),
+        }
+    }
+}
+
+impl TryFrom<StorageReadyFilter> for ExactMembershipFilter {
+    type Error = String;
+
+    fn try_from(stored: StorageReadyFilter) -> Result<Self, Self::Error> {
+        let items: HashSet<String> = serde_json::from_slice(&stored.values)
+            .map_err(|e| format!("Failed to import exact filter with error: {e}"))?;
+
+        Ok(ExactMembershipFilter { items })
+    }
+}
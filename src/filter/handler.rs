@@ -0,0 +1,174 @@
+use crate::constants::{BLOOM_FILTER_FP_RATE, FILTER_SECRET_ID_LEN};
+use crate::filter::bloom::BloomMembershipFilter;
+use crate::filter::cuckoo::CuckooMembershipFilter;
+use crate::filter::exact::ExactMembershipFilter;
+use crate::filter::storage::HasherId;
+use crate::filter::xor::XorMembershipFilter;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Trait for a filter tracking which addresses hold data on this node
+pub trait MembershipFilter: Send + Sync {
+    /// Adds an item to the filter
+    ///
+    /// ### Arguments
+    ///
+    /// * `item` - Item to add
+    fn add(&mut self, item: &str) -> Result<(), String>;
+
+    /// Checks whether an item may be in the filter
+    ///
+    /// ### Arguments
+    ///
+    /// * `item` - Item to look up
+    fn contains(&self, item: &str) -> bool;
+
+    /// Deletes an item from the filter, returning whether it was found
+    ///
+    /// ### Arguments
+    ///
+    /// * `item` - Item to delete
+    fn delete(&mut self, item: &str) -> bool;
+
+    /// Number of items held by the filter
+    fn len(&self) -> usize;
+
+    /// Whether the filter holds no items
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The kind of filter this is
+    fn kind(&self) -> FilterKind;
+
+    /// Number of items the filter is sized for, if it is bounded
+    fn capacity(&self) -> Option<usize>;
+
+    /// Estimated probability that `contains` reports an item which was never added
+    fn false_positive_rate(&self) -> f64;
+
+    /// Collects statistics on the filter's size and accuracy
+    fn stats(&self) -> FilterStats {
+        let capacity = self.capacity();
+        FilterStats {
+            kind: self.kind(),
+            length: self.len(),
+            capacity,
+            load_factor: capacity.map(|c| self.len() as f64 / c as f64),
+            false_positive_rate: self.false_positive_rate(),
+            shards: 1,
+        }
+    }
+
+    /// Exports the filter into a form that can be saved to a store
+    fn export(&self) -> StorageReadyFilter;
+}
+
+/// Available membership filter implementations
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterKind {
+    #[default]
+    Cuckoo,
+    Bloom,
+    Xor,
+    Exact,
+}
+
+impl FilterKind {
+    /// Constructs a new, empty and unkeyed filter of this kind
+    pub fn construct(&self) -> Box<dyn MembershipFilter> {
+        match self {
+            FilterKind::Cuckoo => Box::new(CuckooMembershipFilter::new()),
+            FilterKind::Bloom => Box::new(BloomMembershipFilter::new()),
+            FilterKind::Xor => Box::new(XorMembershipFilter::new()),
+            FilterKind::Exact => Box::new(ExactMembershipFilter::new()),
+        }
+    }
+
+    /// Constructs a new, empty and unkeyed filter of this kind sized for the given capacity
+    ///
+    /// ### Arguments
+    ///
+    /// * `capacity` - Number of items to size the filter for
+    pub fn construct_with_capacity(&self, capacity: usize) -> Box<dyn MembershipFilter> {
+        match self {
+            FilterKind::Cuckoo => Box::new(CuckooMembershipFilter::with_capacity(capacity)),
+            FilterKind::Bloom => Box::new(BloomMembershipFilter::with_capacity(
+                capacity,
+                BLOOM_FILTER_FP_RATE,
+            )),
+            FilterKind::Xor => Box::new(XorMembershipFilter::new()),
+            FilterKind::Exact => Box::new(ExactMembershipFilter::new()),
+        }
+    }
+
+    /// Whether items can be removed from filters of this kind. Bloom and xor
+    /// filters only drop items when they are rebuilt
+    pub fn supports_deletion(&self) -> bool {
+        matches!(self, FilterKind::Cuckoo | FilterKind::Exact)
+    }
+}
+
+impl FromStr for FilterKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "cuckoo" => Ok(FilterKind::Cuckoo),
+            "bloom" => Ok(FilterKind::Bloom),
+            "xor" => Ok(FilterKind::Xor),
+            "exact" => Ok(FilterKind::Exact),
+            _ => Err(format!("Unknown filter type: {s}")),
+        }
+    }
+}
+
+impl fmt::Display for FilterKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FilterKind::Cuckoo => write!(f, "cuckoo"),
+            FilterKind::Bloom => write!(f, "bloom"),
+            FilterKind::Xor => write!(f, "xor"),
+            FilterKind::Exact => write!(f, "exact"),
+        }
+    }
+}
+
+/// Statistics on a membership filter's size and accuracy
+#[derive(Serialize, Debug, Clone)]
+pub struct FilterStats {
+    pub kind: FilterKind,
+    pub length: usize,
+    pub capacity: Option<usize>,
+    pub load_factor: Option<f64>,
+    pub false_positive_rate: f64,
+    pub shards: usize,
+}
+
+// ========== STORAGE SERIALIZATION FOR FILTERS ========== //
+
+/// Exported state of a membership filter, ready to be encoded into a snapshot
+#[derive(Debug, Clone)]
+pub struct StorageReadyFilter {
+    pub kind: FilterKind,
+    pub hasher: HasherId,
+    pub key_id: [u8; FILTER_SECRET_ID_LEN],
+    pub shard_index: u16,
+    pub shard_count: u16,
+    pub values: Vec<u8>,
+    pub length: usize,
+}
+
+impl StorageReadyFilter {
+    /// Reconstructs the filter described by this export
+    pub fn import(self) -> Result<Box<dyn MembershipFilter>, String> {
+        Ok(match self.kind {
+            FilterKind::Cuckoo => Box::new(CuckooMembershipFilter::from(self)),
+            FilterKind::Bloom => Box::new(BloomMembershipFilter::try_from(self)?),
+            FilterKind::Xor => Box::new(XorMembershipFilter::try_from(self)?),
+            FilterKind::Exact => Box::new(ExactMembershipFilter::try_from(self)?),
+        })
+    }
+}
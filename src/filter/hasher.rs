@@ -0,0 +1,34 @@
+use siphasher::sip::SipHasher13;
+use std::hash::{Hash, Hasher};
+
+/// SipHash-1-3 with fixed keys. Unlike `std`'s `DefaultHasher`, its output is
+/// specified and will not change between Rust releases, so filters built with
+/// it remain valid after a toolchain upgrade
+#[derive(Debug, Clone)]
+pub struct StableHasher(SipHasher13);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher(SipHasher13::new_with_keys(0, 0))
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes)
+    }
+
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+}
+
+/// Filter item which hashes as its raw bytes only, avoiding the
+/// unspecified framing `std` adds when hashing a `str`
+pub struct StableKey<'a>(pub &'a [u8]);
+
+impl Hash for StableKey<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write(self.0)
+    }
+}
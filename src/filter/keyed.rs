@@ -0,0 +1,149 @@
+use crate::constants::{FILTER_SECRET_ID_LEN, FILTER_SECRET_LEN};
+use crate::filter::handler::{FilterKind, MembershipFilter, StorageReadyFilter};
+use crate::filter::storage::HasherId;
+use siphasher::sip128::SipHasher24;
+use std::fmt;
+use valence_core::crypto::{generate_random, sha3_256};
+
+/// Per-node secret mixed into every filter hash, so that addresses colliding in
+/// the filter cannot be crafted without knowing it
+#[derive(Clone, PartialEq, Eq)]
+pub struct FilterSecret([u8; FILTER_SECRET_LEN]);
+
+impl FilterSecret {
+    /// Generates a new random secret
+    pub fn generate() -> Self {
+        FilterSecret(generate_random())
+    }
+
+    /// Parses a hex-encoded secret
+    ///
+    /// ### Arguments
+    ///
+    /// * `secret` - Hex string of the secret
+    pub fn from_hex(secret: &str) -> Result<Self, String> {
+        hex::decode(secret)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(FilterSecret)
+            .ok_or(format!(
+                "Filter secret must be {} hex-encoded bytes",
+                FILTER_SECRET_LEN
+            ))
+    }
+
+    /// Hex encoding of the secret
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+
+    /// Identifier of the secret which is safe to store alongside the filter
+    pub fn id(&self) -> [u8; FILTER_SECRET_ID_LEN] {
+        let digest = sha3_256::digest(&self.0);
+        let mut id = [0u8; FILTER_SECRET_ID_LEN];
+        id.copy_from_slice(&digest[..FILTER_SECRET_ID_LEN]);
+        id
+    }
+
+    /// Keyed digest of an item, which is what actually gets inserted into the filter
+    ///
+    /// ### Arguments
+    ///
+    /// * `item` - Item to digest
+    fn digest(&self, item: &str) -> String {
+        let hash = SipHasher24::new_with_key(&self.0).hash(item.as_bytes());
+        hex::encode(hash.as_bytes())
+    }
+
+    /// Picks the shard an item belongs to. Keyed like the digest, so that
+    /// items cannot be steered into a single shard
+    ///
+    /// ### Arguments
+    ///
+    /// * `item` - Item to place
+    /// * `shard_count` - Number of shards to pick from
+    pub fn shard_index(&self, item: &str, shard_count: usize) -> usize {
+        let (_, low) = SipHasher24::new_with_key(&self.0)
+            .hash(item.as_bytes())
+            .as_u64();
+        (low % shard_count.max(1) as u64) as usize
+    }
+}
+
+impl fmt::Debug for FilterSecret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FilterSecret({})", hex::encode(self.id()))
+    }
+}
+
+/// Membership filter which hashes every item with the node's secret before
+/// handing it to the underlying filter
+pub struct KeyedFilter {
+    secret: FilterSecret,
+    inner: Box<dyn MembershipFilter>,
+}
+
+impl KeyedFilter {
+    /// Wraps a filter so that its items are keyed with the given secret
+    ///
+    /// ### Arguments
+    ///
+    /// * `inner` - Filter to hold the keyed items
+    /// * `secret` - Secret to key items with
+    pub fn new(inner: Box<dyn MembershipFilter>, secret: FilterSecret) -> Self {
+        KeyedFilter { secret, inner }
+    }
+
+    /// Reconstructs a keyed filter from an export, failing if it was built with another secret
+    ///
+    /// ### Arguments
+    ///
+    /// * `stored` - Exported filter
+    /// * `secret` - Secret the filter is expected to be keyed with
+    pub fn import(stored: StorageReadyFilter, secret: FilterSecret) -> Result<Self, String> {
+        if stored.hasher != HasherId::KeyedSipHash || stored.key_id != secret.id() {
+            return Err("Stored filter was built with a different filter secret".to_string());
+        }
+
+        Ok(KeyedFilter::new(stored.import()?, secret))
+    }
+}
+
+impl MembershipFilter for KeyedFilter {
+    fn add(&mut self, item: &str) -> Result<(), String> {
+        self.inner.add(&self.secret.digest(item))
+    }
+
+    fn contains(&self, item: &str) -> bool {
+        self.inner.contains(&self.secret.digest(item))
+    }
+
+    fn delete(&mut self, item: &str) -> bool {
+        self.inner.delete(&self.secret.digest(item))
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn kind(&self) -> FilterKind {
+        self.inner.kind()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.inner.capacity()
+    }
+
+    /// Keyed digests are 128 bits, so their own collisions are negligible
+    fn false_positive_rate(&self) -> f64 {
+        self.inner.false_positive_rate()
+    }
+
+    fn export(&self) -> StorageReadyFilter {
+        StorageReadyFilter {
+            hasher: HasherId::KeyedSipHash,
+            key_id: self.secret.id(),
+            ..self.inner.export()
+        }
+    }
+}
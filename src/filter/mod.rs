@@ -0,0 +1,9 @@
+pub mod bloom;
+pub mod cuckoo;
+pub mod exact;
+pub mod handler;
+pub mod hasher;
+pub mod keyed;
+pub mod sharded;
+pub mod storage;
+pub mod xor;
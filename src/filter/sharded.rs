@@ -0,0 +1,219 @@
+use crate::filter::handler::{FilterKind, FilterStats, MembershipFilter, StorageReadyFilter};
+use crate::filter::keyed::{FilterSecret, KeyedFilter};
+use futures::lock::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::RwLock;
+
+/// Membership filter partitioned into independently locked shards.
+///
+/// Items are placed by a hash keyed with the filter secret, so requests for
+/// different addresses rarely contend on the same lock. Each shard tracks whether
+/// it has changed since it was last exported, so only dirty shards need saving.
+///
+/// Lookups hold the secret for reading while they use a shard, so that it can be
+/// swapped together with every shard when the filter is rebuilt under a new secret.
+pub struct ShardedFilter {
+    kind: FilterKind,
+    secret: RwLock<FilterSecret>,
+    shards: Vec<Mutex<KeyedFilter>>,
+    dirty: Vec<AtomicBool>,
+}
+
+impl ShardedFilter {
+    /// Constructs a new, empty filter. Every shard starts dirty, as none has been saved yet
+    ///
+    /// ### Arguments
+    ///
+    /// * `kind` - The kind of filter to use for each shard
+    /// * `secret` - Secret to key items and shard placement with
+    /// * `shard_count` - Number of shards to split the filter into
+    /// * `capacity` - Number of items to size the filter for across all shards
+    pub fn new(
+        kind: FilterKind,
+        secret: FilterSecret,
+        shard_count: usize,
+        capacity: usize,
+    ) -> Self {
+        let shard_count = shard_count.max(1);
+        let shard_capacity = capacity.div_ceil(shard_count);
+
+        let shards = (0..shard_count)
+            .map(|_| {
+                Mutex::new(KeyedFilter::new(
+                    kind.construct_with_capacity(shard_capacity),
+                    secret.clone(),
+                ))
+            })
+            .collect();
+
+        ShardedFilter {
+            kind,
+            secret: RwLock::new(secret),
+            shards,
+            dirty: (0..shard_count).map(|_| AtomicBool::new(true)).collect(),
+        }
+    }
+
+    /// Assembles a filter from shards loaded from a store, all of which start clean
+    ///
+    /// ### Arguments
+    ///
+    /// * `kind` - The kind of filter used by each shard
+    /// * `secret` - Secret the shards are keyed with
+    /// * `shards` - The shards, in index order
+    pub fn from_shards(kind: FilterKind, secret: FilterSecret, shards: Vec<KeyedFilter>) -> Self {
+        ShardedFilter {
+            kind,
+            secret: RwLock::new(secret),
+            dirty: shards.iter().map(|_| AtomicBool::new(false)).collect(),
+            shards: shards.into_iter().map(Mutex::new).collect(),
+        }
+    }
+
+    /// The kind of filter used by each shard
+    pub fn kind(&self) -> FilterKind {
+        self.kind
+    }
+
+    /// Secret the shards are keyed with
+    pub async fn secret(&self) -> FilterSecret {
+        self.secret.read().await.clone()
+    }
+
+    /// Number of shards the filter is split into
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Adds an item to its shard
+    ///
+    /// ### Arguments
+    ///
+    /// * `item` - Item to add
+    pub async fn add(&self, item: &str) -> Result<(), String> {
+        let secret = self.secret.read().await;
+        let index = secret.shard_index(item, self.shards.len());
+        let mut shard = self.shards[index].lock().await;
+        shard.add(item)?;
+        self.dirty[index].store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Checks whether an item may be in its shard
+    ///
+    /// ### Arguments
+    ///
+    /// * `item` - Item to look up
+    pub async fn contains(&self, item: &str) -> bool {
+        let secret = self.secret.read().await;
+        self.shards[secret.shard_index(item, self.shards.len())]
+            .lock()
+            .await
+            .contains(item)
+    }
+
+    /// Deletes an item from its shard, returning whether it was found
+    ///
+    /// ### Arguments
+    ///
+    /// * `item` - Item to delete
+    pub async fn delete(&self, item: &str) -> bool {
+        let secret = self.secret.read().await;
+        let index = secret.shard_index(item, self.shards.len());
+        let mut shard = self.shards[index].lock().await;
+        let found = shard.delete(item);
+        if found {
+            self.dirty[index].store(true, Ordering::Release);
+        }
+        found
+    }
+
+    /// Number of items held across all shards
+    pub async fn len(&self) -> usize {
+        let mut length = 0;
+        for shard in &self.shards {
+            length += shard.lock().await.len();
+        }
+        length
+    }
+
+    /// Whether no shard holds any items
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+
+    /// Collects statistics across all shards. Each lookup only touches one shard,
+    /// so the false positive rate is the mean of the shards' rates
+    pub async fn stats(&self) -> FilterStats {
+        let mut length = 0;
+        let mut capacity = Some(0);
+        let mut false_positive_rate = 0.0;
+
+        for shard in &self.shards {
+            let stats = shard.lock().await.stats();
+            length += stats.length;
+            capacity = capacity.zip(stats.capacity).map(|(a, b)| a + b);
+            false_positive_rate += stats.false_positive_rate;
+        }
+
+        FilterStats {
+            kind: self.kind,
+            length,
+            capacity,
+            load_factor: capacity.map(|c| length as f64 / c as f64),
+            false_positive_rate: false_positive_rate / self.shards.len() as f64,
+            shards: self.shards.len(),
+        }
+    }
+
+    /// Exports every shard changed since the last call, clearing their dirty flags
+    pub async fn take_dirty(&self) -> Vec<StorageReadyFilter> {
+        let mut exports = Vec::new();
+
+        for (index, shard) in self.shards.iter().enumerate() {
+            if self.dirty[index].swap(false, Ordering::AcqRel) {
+                exports.push(StorageReadyFilter {
+                    shard_index: index as u16,
+                    shard_count: self.shards.len() as u16,
+                    ..shard.lock().await.export()
+                });
+            }
+        }
+
+        exports
+    }
+
+    /// Replaces the contents of every shard with those of another filter, e.g. one
+    /// rebuilt from the DB, while the filter stays shared. The other filter's secret
+    /// is taken too, and no lookup runs until every shard is replaced. Every shard
+    /// is left dirty
+    ///
+    /// ### Arguments
+    ///
+    /// * `other` - Filter of the same kind and shard count to take the shards of
+    pub async fn replace(&self, other: ShardedFilter) -> Result<(), String> {
+        if other.kind != self.kind || other.shards.len() != self.shards.len() {
+            return Err("Replacement filter has a different layout".to_string());
+        }
+
+        let mut secret = self.secret.write().await;
+        for (index, shard) in other.shards.into_iter().enumerate() {
+            *self.shards[index].lock().await = shard.into_inner();
+            self.dirty[index].store(true, Ordering::Release);
+        }
+        *secret = other.secret.into_inner();
+
+        Ok(())
+    }
+
+    /// Flags a shard as needing to be saved again, e.g. after a failed write
+    ///
+    /// ### Arguments
+    ///
+    /// * `index` - Index of the shard
+    pub fn mark_dirty(&self, index: usize) {
+        if let Some(dirty) = self.dirty.get(index) {
+            dirty.store(true, Ordering::Release);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::constants::{FILTER_SECRET_ID_LEN, FILTER_SNAPSHOT_MAGIC, FILTER_SNAPSHOT_VERSION};
+use crate::filter::handler::{FilterKind, StorageReadyFilter};
+use serde::{Deserialize, Serialize};
+use valence_core::crypto::sha3_256;
+
+/// Size of the snapshot header in bytes
+const HEADER_LEN: usize = 40;
+
+/// Size of the truncated SHA3-256 checksum in bytes
+const CHECKSUM_LEN: usize = 8;
+
+/// Identifies the hash function a filter was built with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HasherId {
+    /// Fixed-key SipHash-1-3
+    SipHash13 = 1,
+    /// SipHash-2-4 keyed with the node's filter secret, fed into fixed-key SipHash-1-3
+    KeyedSipHash = 2,
+}
+
+impl TryFrom<u8> for HasherId {
+    type Error = String;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        match id {
+            1 => Ok(HasherId::SipHash13),
+            2 => Ok(HasherId::KeyedSipHash),
+            _ => Err(format!("Unknown filter hasher id: {id}")),
+        }
+    }
+}
+
+impl FilterKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            FilterKind::Cuckoo => 0,
+            FilterKind::Bloom => 1,
+            FilterKind::Exact => 2,
+            FilterKind::Xor => 3,
+        }
+    }
+
+    fn from_byte(b: u8) -> Result<Self, String> {
+        match b {
+            0 => Ok(FilterKind::Cuckoo),
+            1 => Ok(FilterKind::Bloom),
+            2 => Ok(FilterKind::Exact),
+            3 => Ok(FilterKind::Xor),
+            _ => Err(format!("Unknown filter kind id: {b}")),
+        }
+    }
+}
+
+/// Persisted form of a membership filter, saved as a single binary blob.
+///
+/// The blob starts with a fixed 40 byte header, all integers little endian:
+///
+/// | Bytes  | Field                                    |
+/// |--------|------------------------------------------|
+/// | 0..4   | Magic, `VLFS`                            |
+/// | 4      | Format version                           |
+/// | 5      | Filter kind                              |
+/// | 6      | Hasher id                                |
+/// | 7      | Reserved, zero                           |
+/// | 8..10  | Index of this shard                      |
+/// | 10..12 | Number of shards in the whole filter     |
+/// | 12..16 | Reserved, zero                           |
+/// | 16..24 | Number of items in the filter            |
+/// | 24..32 | Id of the filter secret, zero if unkeyed |
+/// | 32..40 | Truncated SHA3-256 of the remainder      |
+///
+/// followed by the filter's own exported values.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FilterSnapshot {
+    #[serde(with = "serde_bytes")]
+    pub blob: Vec<u8>,
+}
+
+impl From<&StorageReadyFilter> for FilterSnapshot {
+    fn from(filter: &StorageReadyFilter) -> Self {
+        let mut blob = Vec::with_capacity(HEADER_LEN + filter.values.len());
+        blob.extend_from_slice(FILTER_SNAPSHOT_MAGIC);
+        blob.push(FILTER_SNAPSHOT_VERSION);
+        blob.push(filter.kind.to_byte());
+        blob.push(filter.hasher as u8);
+        blob.push(0);
+        blob.extend_from_slice(&filter.shard_index.to_le_bytes());
+        blob.extend_from_slice(&filter.shard_count.to_le_bytes());
+        blob.extend_from_slice(&[0; 4]);
+        blob.extend_from_slice(&(filter.length as u64).to_le_bytes());
+        blob.extend_from_slice(&filter.key_id);
+        blob.extend_from_slice(&checksum(&blob, &filter.values));
+        blob.extend_from_slice(&filter.values);
+
+        FilterSnapshot { blob }
+    }
+}
+
+impl TryFrom<FilterSnapshot> for StorageReadyFilter {
+    type Error = String;
+
+    fn try_from(snapshot: FilterSnapshot) -> Result<Self, Self::Error> {
+        let blob = snapshot.blob;
+        if blob.len() < HEADER_LEN || &blob[0..4] != FILTER_SNAPSHOT_MAGIC {
+            return Err("Filter snapshot has no valid header".to_string());
+        }
+        if blob[4] != FILTER_SNAPSHOT_VERSION {
+            return Err(format!("Unsupported filter snapshot version: {}", blob[4]));
+        }
+
+        let (header, values) = blob.split_at(HEADER_LEN);
+        if header[32..40] != checksum(&header[..32], values) {
+            return Err("Filter snapshot checksum mismatch".to_string());
+        }
+
+        let shard_index = u16::from_le_bytes([header[8], header[9]]);
+        let shard_count = u16::from_le_bytes([header[10], header[11]]);
+        let mut length = [0u8; 8];
+        length.copy_from_slice(&header[16..24]);
+        let mut key_id = [0u8; FILTER_SECRET_ID_LEN];
+        key_id.copy_from_slice(&header[24..32]);
+
+        Ok(StorageReadyFilter {
+            kind: FilterKind::from_byte(header[5])?,
+            hasher: HasherId::try_from(header[6])?,
+            key_id,
+            shard_index,
+            shard_count,
+            values: values.to_vec(),
+            length: u64::from_le_bytes(length) as usize,
+        })
+    }
+}
+
+/// Computes the snapshot checksum over the header fields and filter values
+fn checksum(header: &[u8], values: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let digest = sha3_256::digest_all([header, values].into_iter());
+    let mut out = [0u8; CHECKSUM_LEN];
+    out.copy_from_slice(&digest[..CHECKSUM_LEN]);
+    out
+}
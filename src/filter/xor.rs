@@ -0,0 +1,268 @@
+use crate::constants::{FILTER_SECRET_ID_LEN, XOR_FILTER_BATCH_SIZE};
+use crate::filter::handler::{FilterKind, MembershipFilter, StorageReadyFilter};
+use crate::filter::hasher::StableHasher;
+use crate::filter::storage::HasherId;
+use std::collections::HashSet;
+use std::hash::Hasher;
+
+/// Number of seeds tried before giving up on building a table
+const MAX_BUILD_ATTEMPTS: u64 = 64;
+
+/// Static xor filter with 16-bit fingerprints, built once over a fixed set of item hashes
+struct XorTable {
+    seed: u64,
+    block_length: usize,
+    fingerprints: Vec<u16>,
+}
+
+impl XorTable {
+    /// Builds a table over the given item hashes, which must be unique
+    ///
+    /// ### Arguments
+    ///
+    /// * `keys` - Item hashes to build the table for
+    fn build(keys: &[u64]) -> Result<Self, String> {
+        let capacity = ((keys.len() as f64 * 1.23) as usize + 32) / 3 * 3;
+        let block_length = capacity / 3;
+
+        for seed in 0..MAX_BUILD_ATTEMPTS {
+            let mut counts = vec![0u32; capacity];
+            let mut masks = vec![0u64; capacity];
+            for &key in keys {
+                let hash = mix(key, seed);
+                for slot in slots(hash, block_length) {
+                    counts[slot] += 1;
+                    masks[slot] ^= hash;
+                }
+            }
+
+            // Peel slots holding a single hash until every hash has a slot of its own
+            let mut queue: Vec<usize> = (0..capacity).filter(|&i| counts[i] == 1).collect();
+            let mut stack = Vec::with_capacity(keys.len());
+            while let Some(slot) = queue.pop() {
+                if counts[slot] != 1 {
+                    continue;
+                }
+
+                let hash = masks[slot];
+                stack.push((slot, hash));
+                for other in slots(hash, block_length) {
+                    counts[other] -= 1;
+                    masks[other] ^= hash;
+                    if counts[other] == 1 {
+                        queue.push(other);
+                    }
+                }
+            }
+
+            if stack.len() != keys.len() {
+                continue;
+            }
+
+            let mut fingerprints = vec![0u16; capacity];
+            for &(slot, hash) in stack.iter().rev() {
+                let [a, b, c] = slots(hash, block_length);
+                fingerprints[slot] =
+                    fingerprint(hash) ^ fingerprints[a] ^ fingerprints[b] ^ fingerprints[c];
+            }
+
+            return Ok(XorTable {
+                seed,
+                block_length,
+                fingerprints,
+            });
+        }
+
+        Err("Failed to build xor filter table".to_string())
+    }
+
+    fn contains(&self, key: u64) -> bool {
+        let hash = mix(key, self.seed);
+        let [a, b, c] = slots(hash, self.block_length);
+        fingerprint(hash) == self.fingerprints[a] ^ self.fingerprints[b] ^ self.fingerprints[c]
+    }
+}
+
+/// Compact probabilistic filter made of static xor filters. An xor filter
+/// cannot take inserts once built, so added items are buffered and sealed
+/// into a new table once the buffer fills. Like bloom filters, items cannot be
+/// removed, so an address stays a (false) positive after its data is deleted
+#[derive(Default)]
+pub struct XorMembershipFilter {
+    tables: Vec<XorTable>,
+    pending: HashSet<u64>,
+    length: usize,
+}
+
+impl XorMembershipFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of buffered items at which they are sealed into a table. Grows
+    /// with the filter so the number of tables stays logarithmic in its size
+    fn batch_limit(&self) -> usize {
+        XOR_FILTER_BATCH_SIZE.max((self.length - self.pending.len()) / 4)
+    }
+
+    /// Builds a table from the buffered items
+    fn seal(&mut self) -> Result<(), String> {
+        let keys: Vec<u64> = self.pending.iter().copied().collect();
+        self.tables.push(XorTable::build(&keys)?);
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+impl MembershipFilter for XorMembershipFilter {
+    fn add(&mut self, item: &str) -> Result<(), String> {
+        if self.contains(item) {
+            return Ok(());
+        }
+
+        self.pending.insert(item_hash(item));
+        self.length += 1;
+
+        if self.pending.len() >= self.batch_limit() {
+            self.seal()?;
+        }
+        Ok(())
+    }
+
+    fn contains(&self, item: &str) -> bool {
+        let key = item_hash(item);
+        self.pending.contains(&key) || self.tables.iter().any(|t| t.contains(key))
+    }
+
+    /// Items cannot be removed from an xor filter, so this only reports membership
+    fn delete(&mut self, item: &str) -> bool {
+        self.contains(item)
+    }
+
+    fn len(&self) -> usize {
+        self.length
+    }
+
+    fn kind(&self) -> FilterKind {
+        FilterKind::Xor
+    }
+
+    /// Tables are added as the filter grows, so it has no fixed capacity
+    fn capacity(&self) -> Option<usize> {
+        None
+    }
+
+    /// `1 - (1 - 2^-16)^t` for `t` tables with 16 bit fingerprints. Buffered
+    /// items are held exactly, so they add no false positives
+    fn false_positive_rate(&self) -> f64 {
+        1.0 - (1.0 - 2f64.powi(-16)).powi(self.tables.len() as i32)
+    }
+
+    /// Exported as the table count, then each table's seed, fingerprint count
+    /// and fingerprints, followed by the buffered item hashes. All little-endian
+    fn export(&self) -> StorageReadyFilter {
+        let mut values = Vec::new();
+        values.extend_from_slice(&(self.tables.len() as u32).to_le_bytes());
+        for table in &self.tables {
+            values.extend_from_slice(&table.seed.to_le_bytes());
+            values.extend_from_slice(&(table.fingerprints.len() as u32).to_le_bytes());
+            for fp in &table.fingerprints {
+                values.extend_from_slice(&fp.to_le_bytes());
+            }
+        }
+        for key in &self.pending {
+            values.extend_from_slice(&key.to_le_bytes());
+        }
+
+        StorageReadyFilter {
+            kind: FilterKind::Xor,
+            hasher: HasherId::SipHash13,
+            key_id: [0; FILTER_SECRET_ID_LEN],
+            shard_index: 0,
+            shard_count: 1,
+            values,
+            length: self.length,
+        }
+    }
+}
+
+impl TryFrom<StorageReadyFilter> for XorMembershipFilter {
+    type Error = String;
+
+    fn try_from(stored: StorageReadyFilter) -> Result<Self, Self::Error> {
+        let malformed = || "Failed to import xor filter: malformed export".to_string();
+        let mut rest = stored.values.as_slice();
+        let mut take = |n: usize| -> Result<&[u8], String> {
+            if rest.len() < n {
+                return Err(malformed());
+            }
+            let (head, tail) = rest.split_at(n);
+            rest = tail;
+            Ok(head)
+        };
+
+        let num_tables = u32::from_le_bytes(take(4)?.try_into().map_err(|_| malformed())?);
+        let mut tables = Vec::new();
+        for _ in 0..num_tables {
+            let seed = u64::from_le_bytes(take(8)?.try_into().map_err(|_| malformed())?);
+            let capacity = u32::from_le_bytes(take(4)?.try_into().map_err(|_| malformed())?);
+            if capacity == 0 || !capacity.is_multiple_of(3) {
+                return Err(malformed());
+            }
+
+            let fingerprints = take(capacity as usize * 2)?
+                .chunks_exact(2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                .collect();
+
+            tables.push(XorTable {
+                seed,
+                block_length: capacity as usize / 3,
+                fingerprints,
+            });
+        }
+
+        if !rest.len().is_multiple_of(8) {
+            return Err(malformed());
+        }
+        let pending = rest
+            .chunks_exact(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap_or_default()))
+            .collect();
+
+        Ok(XorMembershipFilter {
+            tables,
+            pending,
+            length: stored.length,
+        })
+    }
+}
+
+/// Hashes an item into the key used by the tables
+fn item_hash(item: &str) -> u64 {
+    let mut hasher = StableHasher::default();
+    hasher.write(item.as_bytes());
+    hasher.finish()
+}
+
+/// Mixes a key with a table seed. Bijective, so distinct keys never collide
+fn mix(key: u64, seed: u64) -> u64 {
+    let mut h = key.wrapping_add(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    h = (h ^ (h >> 33)).wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    h = (h ^ (h >> 33)).wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+    h ^ (h >> 33)
+}
+
+/// The three slots, one per block, a hash maps to
+fn slots(hash: u64, block_length: usize) -> [usize; 3] {
+    let reduce = |h: u64| ((h as u32 as u64 * block_length as u64) >> 32) as usize;
+    [
+        reduce(hash),
+        block_length + reduce(hash.rotate_left(21)),
+        2 * block_length + reduce(hash.rotate_left(42)),
+    ]
+}
+
+fn fingerprint(hash: u64) -> u16 {
+    (hash ^ (hash >> 32)) as u16
+}
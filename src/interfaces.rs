@@ -1,5 +1,21 @@
+use crate::blocklist::{BlockKind, Blocklist};
+use crate::encryption::Keyring;
+use crate::filter::handler::{FilterKind, FilterStats};
+use crate::filter::sharded::ShardedFilter;
+use crate::logging::{LogFormat, LogRotation};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing_subscriber::filter::LevelFilter;
+use warp::hyper::body::Bytes;
+
+// ========= TYPE ABSTRACTIONS ========= //
+
+pub type FilterConnection = Arc<ShardedFilter>;
+/// Keys for encryption at rest, absent if it is disabled
+pub type KeyringConnection = Option<Arc<Keyring>>;
+pub type BlocklistConnection = Arc<Blocklist>;
 
 // Define a struct to hold the data (public key, address, signature)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,25 +30,343 @@ pub struct SetRequestData {
     pub data_id: String,
 }
 
+/// Body of a request to block an address or public key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockRequest {
+    pub kind: BlockKind,
+    pub value: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Body of a request to add a sender to an address's allowlist
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllowRequest {
+    /// Public key of the sender
+    pub sender: String,
+}
+
+/// Query of a request to rebuild the membership filter
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RebuildQuery {
+    /// Whether to rebuild under a newly generated filter secret
+    #[serde(default)]
+    pub rotate: bool,
+}
+
+/// A sender exempt from proof of work when writing to an address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllowEntry {
+    /// Public key of the sender
+    pub sender: String,
+    pub allowed_at: String,
+}
+
+/// Proof-of-work headers of a `set_data` request
+#[derive(Debug, Clone)]
+pub struct ProofOfWork {
+    /// Signed timestamp of the request, which the stamp is bound to
+    pub timestamp: String,
+    /// Signed nonce of the request, which the stamp is bound to
+    pub nonce: String,
+    /// Raw body of the request, whose digest the stamp is bound to
+    pub body: Bytes,
+    pub stamp: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SetSaveData {
     pub address: String,
     pub data: Value,
+    /// Public key of the sender, absent for entries saved before it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender: Option<String>,
+    /// Unix time in seconds the entry was saved, which retention counts from
+    pub saved_at: u64,
 }
 
-pub struct EnvConfig {
-    pub debug: bool,
+/// Settings for verifying signed requests
+#[derive(Debug, Clone, Copy)]
+pub struct SignatureConfig {
+    /// Seconds a request timestamp stays valid either side of server time
+    pub replay_window: u64,
+    /// Whether to also accept signatures over the headers alone
+    pub legacy_signatures: bool,
+}
+
+/// Token bucket holding up to `capacity` requests, refilled continuously
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub capacity: u64,
+    pub refill_per_second: f64,
+}
+
+/// Rate limits applied to a single route. Each is optional
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RouteRateLimits {
+    /// Limit per sender, identified by their public key
+    pub sender: Option<RateLimit>,
+    /// Limit per client IP
+    pub ip: Option<RateLimit>,
+}
+
+/// Rate limits for each of the base routes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub get_data: RouteRateLimits,
+    pub set_data: RouteRateLimits,
+    pub del_data: RouteRateLimits,
+    pub usage: RouteRateLimits,
+    pub allowlist: RouteRateLimits,
+}
+
+/// Storage limits per recipient address, and per sender within an address.
+/// Limits left unset are not enforced
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuotaConfig {
+    pub max_entries: Option<u64>,
+    pub max_bytes: Option<u64>,
+    pub max_entries_per_sender: Option<u64>,
+    pub max_bytes_per_sender: Option<u64>,
+}
+
+/// Settings for storing data sent to `set_data`
+#[derive(Debug, Clone)]
+pub struct SetDataConfig {
+    /// The maximum size of a request body
+    pub body_limit: u64,
+    /// Seconds entries stay in the cache
+    pub cache_ttl: usize,
+    pub quota: QuotaConfig,
+    /// Keys to encrypt data with, if encryption at rest is enabled
+    pub keyring: KeyringConnection,
+    /// Leading zero bits required of proof-of-work stamps from senders not on the
+    /// recipient's allowlist, or 0 to not require stamps
+    pub pow_difficulty: u8,
+}
+
+/// Settings for connecting to Redis
+#[derive(Debug, Clone, Default)]
+pub struct CacheConnConfig {
+    /// Host, optionally prefixed with a `redis://` or `rediss://` scheme
+    pub url: String,
+    pub port: String,
+    /// ACL username, or the default user if absent
+    pub user: Option<String>,
+    /// Password for AUTH, or none if empty
+    pub password: String,
+    /// Whether to connect over TLS, i.e. with `rediss://`
+    pub tls: bool,
+}
+
+/// Settings for connecting to MongoDB
+#[derive(Debug, Clone, Default)]
+pub struct DbConnConfig {
+    /// Scheme, e.g. `mongodb://` or `mongodb+srv://`
+    pub protocol: String,
+    pub url: String,
+    /// Port, which is left out for `mongodb+srv://` connections
+    pub port: String,
+    /// Username, or no authentication if empty
+    pub user: String,
+    pub password: String,
+    /// Database the user is defined in
+    pub auth_source: Option<String>,
+    pub tls: bool,
+    /// Path of a PEM CA bundle to verify the server with, instead of the system roots
+    pub tls_ca_file: Option<String>,
+    /// Path of a PEM client certificate and key to present to the server
+    pub tls_cert_key_file: Option<String>,
+}
+
+/// Settings for serving over TLS
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    /// Path of the PEM certificate chain
+    pub cert_path: String,
+    /// Path of the PEM private key
+    pub key_path: String,
+    /// Path of the PEM CA certificates client certificates must chain to.
+    /// Client certificates are not requested if absent
+    pub client_ca_path: Option<String>,
+    /// Seconds between checks for changed certificate files
+    pub reload_interval: u64,
+}
+
+/// Settings for where and how log lines are written
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    /// Most verbose level logged
+    pub level: LevelFilter,
+    pub format: LogFormat,
+    /// Path of the file to log to, with the time of each rotation appended.
+    /// Logs go to stdout if absent
+    pub file: Option<String>,
+    pub rotation: LogRotation,
+    /// Number of rotated files kept, or all of them if absent
+    pub max_files: Option<usize>,
+}
+
+/// Settings for exporting traces
+#[derive(Debug, Clone, Default)]
+pub struct TelemetryConfig {
+    /// URL of the OTLP collector to export spans to over gRPC. Spans are not
+    /// exported if absent
+    pub otlp_endpoint: Option<String>,
+    /// Name the node's spans are reported under
+    pub service_name: String,
+}
+
+/// What to keep out of logs. Everything is redacted unless configured otherwise
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    /// Data sent to and retrieved from the node
+    pub payloads: bool,
+    /// Request signatures
+    pub signatures: bool,
+    /// Admin keys and passwords in connection URIs
+    pub credentials: bool,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        RedactionConfig {
+            payloads: true,
+            signatures: true,
+            credentials: true,
+        }
+    }
+}
+
+/// Number of entries and bytes of data held
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub entries: u64,
+    pub bytes: u64,
+}
+
+/// Storage used by an address, in total and by each sender, with its quota
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AddressUsage {
+    pub total: Usage,
+    pub senders: HashMap<String, Usage>,
+    pub quota: QuotaConfig,
+}
+
+/// Approximate size of the data held by a store
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StoreStats {
+    pub addresses: u64,
+    pub entries: u64,
+    pub bytes: u64,
+}
+
+/// Filter and storage statistics reported to node operators
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeStats {
+    pub filter: FilterStats,
+    pub db: StoreStats,
+    pub cache: StoreStats,
+}
+
+/// Outcome of checking that a store can be reached
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyCheck {
+    pub ready: bool,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Whether the membership filter is loaded and can answer lookups
+#[derive(Debug, Clone, Serialize)]
+pub struct FilterCheck {
+    pub ready: bool,
+    pub items: usize,
+}
+
+/// Readiness of the node and each of its dependencies, reported to orchestrators.
+/// The node is ready but degraded while it serves from the DB without the cache
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub degraded: bool,
+    pub db: DependencyCheck,
+    pub cache: DependencyCheck,
+    pub filter: FilterCheck,
+}
+
+/// Node settings reported to operators. Secrets such as passwords and keys are left out
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigView {
     pub extern_port: u16,
-    pub db_protocol: String,
-    pub db_user: String,
+    pub admin_port: Option<u16>,
+    pub tls: bool,
+    pub tls_client_auth: bool,
     pub db_url: String,
     pub db_port: String,
-    pub db_password: String,
+    pub db_user: String,
+    pub db_auth_source: Option<String>,
+    pub db_tls: bool,
     pub cache_url: String,
     pub cache_port: String,
-    pub cache_password: String,
+    pub cache_user: Option<String>,
+    pub cache_tls: bool,
+    pub body_limit: u64,
+    pub cache_ttl: usize,
+    pub replay_window: u64,
+    pub legacy_signatures: bool,
+    pub pow_difficulty: u8,
+    pub rate_limits: RateLimitConfig,
+    pub quotas: QuotaConfig,
+    pub redaction: RedactionConfig,
+    pub filter_type: FilterKind,
+    pub filter_shards: usize,
+    pub encryption: bool,
+    pub reencrypt_interval: u64,
+    pub shutdown_timeout: u64,
+    pub migrate_on_startup: bool,
+    pub connect_attempts: u32,
+    pub sweep_interval: u64,
+    pub data_retention: Option<u64>,
+    pub log_level: String,
+    pub log_format: LogFormat,
+    pub log_file: Option<String>,
+    pub otlp_endpoint: Option<String>,
+    pub market: bool,
+}
+
+pub struct EnvConfig {
+    pub debug: bool,
+    pub extern_port: u16,
+    pub admin_port: Option<u16>,
+    pub tls: Option<TlsConfig>,
+    pub db: DbConnConfig,
+    pub cache: CacheConnConfig,
     pub body_limit: u64,
     pub cache_ttl: usize,
+    pub replay_window: u64,
+    pub legacy_signatures: bool,
+    pub pow_difficulty: u8,
+    pub rate_limits: RateLimitConfig,
+    pub quotas: QuotaConfig,
+    pub redaction: RedactionConfig,
+    pub logging: LogConfig,
+    pub telemetry: TelemetryConfig,
+    pub filter_type: FilterKind,
+    pub filter_shards: usize,
+    pub filter_secret: Option<String>,
+    pub encryption_keyfile: Option<String>,
+    pub reencrypt_interval: u64,
+    pub shutdown_timeout: u64,
+    pub migrate_on_startup: bool,
+    pub connect_attempts: u32,
+    pub sweep_interval: u64,
+    pub data_retention: Option<u64>,
+    pub admin_key: Option<String>,
 
     pub market: bool,
 }
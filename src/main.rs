@@ -1,76 +1,386 @@
 // main.rs
 pub mod api;
+pub mod archive;
+pub mod blocklist;
+pub mod cli;
 pub mod constants;
 pub mod db;
+pub mod encryption;
+pub mod filter;
 pub mod interfaces;
+pub mod logging;
+pub mod metrics;
+pub mod shutdown;
+pub mod sweeper;
+pub mod telemetry;
+pub mod tls;
 pub mod utils;
 
 #[cfg(test)]
 pub mod tests;
 
 use crate::api::routes::*;
+use crate::api::utils::handle_rejection;
+use crate::archive::{run_export, run_import};
+use crate::cli::{parse_command, Command, USAGE};
+use crate::constants::CACHE_RECONNECT_INTERVAL;
+use crate::db::resilient::restore_cache;
+use crate::db::schema::migrate_records;
+use crate::interfaces::{SetDataConfig, SignatureConfig};
+use crate::logging::{init_redaction, redact_url};
+use crate::metrics::record_request;
+use crate::shutdown::{wait_for_signal, Shutdown};
+use crate::sweeper::sweep;
+use crate::telemetry::{init_tracing, request_span};
+use crate::tls::serve;
 use crate::utils::{
-    construct_mongodb_conn, construct_redis_conn, init_cuckoo_filter, load_config, print_welcome,
+    construct_config_view, construct_mongodb_conn, construct_mongodb_url, construct_redis_conn,
+    construct_redis_url, init_filter, init_filter_secret, init_keyring, load_blocklist,
+    load_config, print_welcome, reencrypt_data, save_filter_to_disk,
 };
 
-use futures::lock::Mutex;
 use std::sync::Arc;
-use tracing::info;
-use valence_core::api::utils::handle_rejection;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
 
 use warp::Filter;
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    let command = match parse_command(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
 
     let config = load_config();
-    let cache_addr = format!("{}:{}", config.cache_url, config.cache_port);
-    let db_addr = format!(
-        "{}{}:{}@{}:{}",
-        config.db_protocol, config.db_user, config.db_password, config.db_url, config.db_port
+    let tracing_guard = match init_tracing(&config.logging, &config.telemetry) {
+        Ok(guard) => guard,
+        Err(e) => panic!("Failed to initialize tracing with error: {}", e),
+    };
+    let config_view = construct_config_view(&config);
+    init_redaction(config.redaction);
+
+    let cache_addr = construct_redis_url(&config.cache);
+    let db_addr = construct_mongodb_url(&config.db);
+
+    info!("Connecting to Redis at {}", redact_url(&cache_addr));
+    info!("Connecting to MongoDB at {}", redact_url(&db_addr));
+
+    let (cache_conn, db_conn) = futures::join!(
+        construct_redis_conn(&cache_addr, config.connect_attempts),
+        construct_mongodb_conn(&db_addr, config.connect_attempts)
     );
-    //let db_addr = format!("{}{}:{}", config.db_protocol, config.db_url, config.db_port);
+    let db_conn = match db_conn {
+        Ok(db_conn) => db_conn,
+        Err(e) => panic!("{}", e),
+    };
+
+    if config.migrate_on_startup {
+        if let Err(e) = migrate_records(db_conn.clone()).await {
+            panic!("Failed to migrate stored records with error: {}", e);
+        }
+    }
+
+    let filter_secret =
+        match init_filter_secret(config.filter_secret.as_deref(), db_conn.clone()).await {
+            Ok(secret) => secret,
+            Err(e) => panic!("Failed to initialize filter secret with error: {}", e),
+        };
+
+    let filter_import = match init_filter(
+        config.filter_type,
+        filter_secret,
+        config.filter_shards,
+        db_conn.clone(),
+    )
+    .await
+    {
+        Ok(filter) => filter,
+        Err(e) => panic!("Failed to initialize membership filter with error: {}", e),
+    };
+    let filter = Arc::new(filter_import);
+
+    info!("{} filter initialized successfully", config.filter_type);
+
+    // Archive commands run against the stores and exit without serving
+    let archive_result = match &command {
+        Command::Serve => None,
+        Command::Export(path) => Some(run_export(path, db_conn.clone()).await),
+        Command::Import(path) => {
+            Some(run_import(path, db_conn.clone(), cache_conn.clone(), &filter).await)
+        }
+    };
+    if let Some(result) = archive_result {
+        if let Err(e) = &result {
+            error!("{}", e);
+        }
+        tracing_guard.shutdown().await;
+        std::process::exit(if result.is_ok() { 0 } else { 1 });
+    }
+
+    let keyring = match init_keyring(config.encryption_keyfile.as_deref()) {
+        Ok(keyring) => keyring,
+        Err(e) => panic!("Failed to initialize encryption keys with error: {}", e),
+    };
+
+    let shutdown = Shutdown::new();
+    let drain_timeout = Duration::from_secs(config.shutdown_timeout);
 
-    info!("Connecting to Redis at {}", cache_addr);
-    info!("Connecting to MongoDB at {}", db_addr);
+    let signal_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        info!(
+            "Shutting down, giving requests in flight up to {}s to complete",
+            drain_timeout.as_secs()
+        );
+        signal_shutdown.trigger();
+    });
 
-    let cache_conn = construct_redis_conn(&cache_addr).await;
-    let db_conn = construct_mongodb_conn(&db_addr).await;
+    // Periodically bring stored data under the active key, stopping between passes on shutdown
+    let reencrypt_task = keyring.clone().map(|keyring| {
+        let db = db_conn.clone();
+        let shutdown = shutdown.clone();
+        let period = Duration::from_secs(config.reencrypt_interval.max(1));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.wait() => break,
+                }
+                if let Err(e) = reencrypt_data(&keyring, db.clone()).await {
+                    error!("{}", e);
+                }
+            }
+        })
+    });
 
-    let cf_import = match init_cuckoo_filter(db_conn.clone()).await {
-        Ok(cf) => cf,
-        Err(e) => panic!("Failed to initialize cuckoo filter with error: {}", e),
+    // Keep trying to reach the cache while it is unavailable, stopping on shutdown
+    let cache_task = {
+        let cache = cache_conn.clone();
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(CACHE_RECONNECT_INTERVAL));
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.wait() => break,
+                }
+                if let Err(e) = restore_cache(&cache).await {
+                    debug!("{}", e);
+                }
+            }
+        })
     };
-    let cuckoo_filter = Arc::new(Mutex::new(cf_import));
 
-    info!("Cuckoo filter initialized successfully");
+    // Periodically remove expired, empty and aged-out data, stopping between sweeps on shutdown
+    let sweep_task = (config.sweep_interval > 0).then(|| {
+        let db = db_conn.clone();
+        let cache = cache_conn.clone();
+        let filter = filter.clone();
+        let shutdown = shutdown.clone();
+        let period = Duration::from_secs(config.sweep_interval);
+        let retention = config.data_retention;
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.wait() => break,
+                }
+                if let Err(e) = sweep(db.clone(), cache.clone(), &filter, retention).await {
+                    error!("{}", e);
+                }
+            }
+        })
+    });
 
-    let routes = get_data_with_id(db_conn.clone(), cache_conn.clone(), cuckoo_filter.clone())
-        .or(get_data(
+    let blocklist = match load_blocklist(db_conn.clone()).await {
+        Ok(blocklist) => Arc::new(blocklist),
+        Err(e) => panic!("Failed to load blocklist with error: {}", e),
+    };
+
+    let sig_config = SignatureConfig {
+        replay_window: config.replay_window,
+        legacy_signatures: config.legacy_signatures,
+    };
+
+    let set_config = SetDataConfig {
+        body_limit: config.body_limit,
+        cache_ttl: config.cache_ttl,
+        quota: config.quotas,
+        keyring: keyring.clone(),
+        pow_difficulty: config.pow_difficulty,
+    };
+
+    let routes = get_data_with_id(
+        db_conn.clone(),
+        cache_conn.clone(),
+        filter.clone(),
+        sig_config,
+        config.rate_limits.get_data,
+        keyring.clone(),
+        blocklist.clone(),
+    )
+    .or(get_data(
+        db_conn.clone(),
+        cache_conn.clone(),
+        filter.clone(),
+        sig_config,
+        config.rate_limits.get_data,
+        keyring.clone(),
+        blocklist.clone(),
+    ))
+    .or(set_data(
+        db_conn.clone(),
+        cache_conn.clone(),
+        filter.clone(),
+        set_config,
+        sig_config,
+        config.rate_limits.set_data,
+        blocklist.clone(),
+    ))
+    .or(del_data(
+        db_conn.clone(),
+        cache_conn.clone(),
+        filter.clone(),
+        sig_config,
+        config.rate_limits.del_data,
+        blocklist.clone(),
+    ))
+    .or(usage(
+        db_conn.clone(),
+        cache_conn.clone(),
+        sig_config,
+        config.rate_limits.usage,
+        config.quotas,
+        blocklist.clone(),
+    ))
+    .or(get_allowlist(
+        db_conn.clone(),
+        cache_conn.clone(),
+        sig_config,
+        config.rate_limits.allowlist,
+        blocklist.clone(),
+    ))
+    .or(allow_sender(
+        db_conn.clone(),
+        cache_conn.clone(),
+        sig_config,
+        config.rate_limits.allowlist,
+        blocklist.clone(),
+        config.body_limit,
+    ))
+    .or(disallow_sender(
+        db_conn.clone(),
+        cache_conn.clone(),
+        sig_config,
+        config.rate_limits.allowlist,
+        blocklist.clone(),
+    ))
+    .or(healthz())
+    .or(readyz(db_conn.clone(), cache_conn.clone(), filter.clone()));
+
+    let admin_routes = metrics(filter.clone(), config.admin_key.clone())
+        .or(stats(
             db_conn.clone(),
             cache_conn.clone(),
-            cuckoo_filter.clone(),
+            filter.clone(),
+            config.admin_key.clone(),
         ))
-        .or(set_data(
+        .or(purge_address(
             db_conn.clone(),
             cache_conn.clone(),
-            cuckoo_filter.clone(),
-            config.body_limit,
-            config.cache_ttl,
+            filter.clone(),
+            config.admin_key.clone(),
         ))
-        .or(del_data(
+        .or(rebuild_filter(
             db_conn.clone(),
-            cache_conn.clone(),
-            cuckoo_filter.clone(),
+            filter.clone(),
+            config.admin_key.clone(),
+            config.filter_secret.is_some(),
         ))
-        .recover(handle_rejection);
+        .or(inspect_config(config_view, config.admin_key.clone()))
+        .or(list_blocklist(blocklist.clone(), config.admin_key.clone()))
+        .or(block(
+            db_conn.clone(),
+            blocklist.clone(),
+            config.admin_key.clone(),
+        ))
+        .or(unblock(
+            db_conn.clone(),
+            blocklist.clone(),
+            config.admin_key.clone(),
+        ));
 
-    print_welcome(&db_addr, &cache_addr);
+    print_welcome(&redact_url(&db_addr), &redact_url(&cache_addr));
 
     info!("Server running at localhost:{}", config.extern_port);
 
-    warp::serve(routes)
-        .run(([0, 0, 0, 0], config.extern_port))
-        .await;
+    match config.admin_port {
+        Some(admin_port) => {
+            info!("Admin routes running at localhost:{}", admin_port);
+            let admin_server = tokio::spawn(serve(
+                admin_routes
+                    .recover(handle_rejection)
+                    .with(warp::log::custom(record_request))
+                    .with(warp::trace(request_span)),
+                admin_port,
+                config.tls.clone(),
+                shutdown.clone(),
+                drain_timeout,
+            ));
+            serve(
+                routes
+                    .recover(handle_rejection)
+                    .with(warp::log::custom(record_request))
+                    .with(warp::trace(request_span)),
+                config.extern_port,
+                config.tls,
+                shutdown.clone(),
+                drain_timeout,
+            )
+            .await;
+            let _ = admin_server.await;
+        }
+        None => {
+            serve(
+                routes
+                    .or(admin_routes)
+                    .recover(handle_rejection)
+                    .with(warp::log::custom(record_request))
+                    .with(warp::trace(request_span)),
+                config.extern_port,
+                config.tls,
+                shutdown.clone(),
+                drain_timeout,
+            )
+            .await
+        }
+    }
+
+    // No more requests are being served, so flush what is left to the DB
+    if let Some(task) = reencrypt_task {
+        if tokio::time::timeout(drain_timeout, task).await.is_err() {
+            warn!("Re-encryption pass still running on shutdown, it will resume on restart");
+        }
+    }
+
+    cache_task.abort();
+
+    if let Some(task) = sweep_task {
+        if tokio::time::timeout(drain_timeout, task).await.is_err() {
+            warn!("Sweep still running on shutdown, it will run again after restart");
+        }
+    }
+
+    if let Err(e) = save_filter_to_disk(&filter, db_conn.clone()).await {
+        error!("{}", e);
+    }
+
+    info!("Shutdown complete");
+    tracing_guard.shutdown().await;
 }
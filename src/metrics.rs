@@ -0,0 +1,318 @@
+use crate::constants::{METRICS_NAMESPACE, METRICS_ROUTES, REQUEST_DURATION_BUCKETS};
+use crate::filter::sharded::ShardedFilter;
+use crate::sweeper::SweepReport;
+use prometheus::{
+    Encoder, Gauge, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    Opts, Registry, TextEncoder,
+};
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+// ========== REGISTRY ========== //
+
+/// Metrics collected by the node and exposed on `/metrics`
+pub struct Metrics {
+    registry: Registry,
+    /// Requests served, by route and status
+    pub requests: IntCounterVec,
+    /// Time taken to serve requests, by route and status
+    pub request_duration: HistogramVec,
+    /// Cache lookups made by `get_data`, by outcome
+    pub cache_lookups: IntCounterVec,
+    /// `get_data` requests answered from the DB after a cache miss or error
+    pub db_fallbacks: IntCounter,
+    /// Lookups passed by the membership filter for addresses holding no data
+    pub filter_false_lookups: IntCounter,
+    pub filter_items: IntGauge,
+    pub filter_capacity: IntGauge,
+    pub filter_false_positive_rate: Gauge,
+    /// Time taken to save the membership filter to the DB
+    pub filter_save_duration: Histogram,
+    /// Errors returned by the DB and cache, by backend and operation
+    pub backend_errors: IntCounterVec,
+    /// 1 while the cache is unavailable and requests are served from the DB
+    pub cache_degraded: IntGauge,
+    /// Keys, entries and filter entries removed by the sweeper, by kind
+    pub sweep_removals: IntCounterVec,
+    /// Time taken by sweeps
+    pub sweep_duration: Histogram,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let opts = |name: &str, help: &str| Opts::new(name, help).namespace(METRICS_NAMESPACE);
+        let histogram_opts = |name: &str, help: &str| {
+            HistogramOpts::new(name, help)
+                .namespace(METRICS_NAMESPACE)
+                .buckets(REQUEST_DURATION_BUCKETS.to_vec())
+        };
+
+        // Metric definitions are fixed, so these can only fail on a programming error
+        let metrics = Metrics {
+            registry: Registry::new(),
+            requests: IntCounterVec::new(
+                opts("http_requests_total", "Requests served"),
+                &["route", "status"],
+            )
+            .unwrap(),
+            request_duration: HistogramVec::new(
+                histogram_opts(
+                    "http_request_duration_seconds",
+                    "Time taken to serve requests",
+                ),
+                &["route", "status"],
+            )
+            .unwrap(),
+            cache_lookups: IntCounterVec::new(
+                opts("cache_lookups_total", "Cache lookups made by get_data"),
+                &["outcome"],
+            )
+            .unwrap(),
+            db_fallbacks: IntCounter::with_opts(opts(
+                "db_fallbacks_total",
+                "get_data requests answered from the DB instead of the cache",
+            ))
+            .unwrap(),
+            filter_false_lookups: IntCounter::with_opts(opts(
+                "filter_false_lookups_total",
+                "Lookups passed by the membership filter for addresses holding no data",
+            ))
+            .unwrap(),
+            filter_items: IntGauge::with_opts(opts(
+                "filter_items",
+                "Items held by the membership filter",
+            ))
+            .unwrap(),
+            filter_capacity: IntGauge::with_opts(opts(
+                "filter_capacity",
+                "Items the membership filter is sized for, or 0 if unbounded",
+            ))
+            .unwrap(),
+            filter_false_positive_rate: Gauge::with_opts(opts(
+                "filter_false_positive_rate",
+                "Estimated false positive rate of the membership filter",
+            ))
+            .unwrap(),
+            filter_save_duration: Histogram::with_opts(histogram_opts(
+                "filter_save_duration_seconds",
+                "Time taken to save the membership filter to the DB",
+            ))
+            .unwrap(),
+            backend_errors: IntCounterVec::new(
+                opts(
+                    "backend_errors_total",
+                    "Errors returned by the DB and cache",
+                ),
+                &["backend", "operation"],
+            )
+            .unwrap(),
+            cache_degraded: IntGauge::with_opts(opts(
+                "cache_degraded",
+                "1 while the cache is unavailable and requests are served from the DB",
+            ))
+            .unwrap(),
+            sweep_removals: IntCounterVec::new(
+                opts(
+                    "sweep_removals_total",
+                    "Keys, entries and filter entries removed by the sweeper",
+                ),
+                &["kind"],
+            )
+            .unwrap(),
+            sweep_duration: Histogram::with_opts(histogram_opts(
+                "sweep_duration_seconds",
+                "Time taken by sweeps",
+            ))
+            .unwrap(),
+        };
+
+        for collector in [
+            Box::new(metrics.requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(metrics.request_duration.clone()),
+            Box::new(metrics.cache_lookups.clone()),
+            Box::new(metrics.db_fallbacks.clone()),
+            Box::new(metrics.filter_false_lookups.clone()),
+            Box::new(metrics.filter_items.clone()),
+            Box::new(metrics.filter_capacity.clone()),
+            Box::new(metrics.filter_false_positive_rate.clone()),
+            Box::new(metrics.filter_save_duration.clone()),
+            Box::new(metrics.backend_errors.clone()),
+            Box::new(metrics.cache_degraded.clone()),
+            Box::new(metrics.sweep_removals.clone()),
+            Box::new(metrics.sweep_duration.clone()),
+        ] {
+            metrics.registry.register(collector).unwrap();
+        }
+
+        metrics
+    }
+}
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// Metrics for the process
+pub fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// Renders the process's metrics in the Prometheus text format, refreshing
+/// the membership filter's statistics first
+///
+/// ### Arguments
+///
+/// * `filter` - The membership filter
+pub async fn render_metrics(filter: &ShardedFilter) -> Result<String, String> {
+    let metrics = metrics();
+    let stats = filter.stats().await;
+    metrics.filter_items.set(stats.length as i64);
+    metrics
+        .filter_capacity
+        .set(stats.capacity.unwrap_or_default() as i64);
+    metrics
+        .filter_false_positive_rate
+        .set(stats.false_positive_rate);
+
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&metrics.registry.gather(), &mut buffer)
+        .map_err(|e| format!("Failed to encode metrics with error: {}", e))?;
+
+    String::from_utf8(buffer).map_err(|e| format!("Failed to encode metrics with error: {}", e))
+}
+
+// ========== RECORDING ========== //
+
+/// Outcome of a cache lookup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheOutcome {
+    Hit,
+    Miss,
+    Error,
+}
+
+impl fmt::Display for CacheOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CacheOutcome::Hit => write!(f, "hit"),
+            CacheOutcome::Miss => write!(f, "miss"),
+            CacheOutcome::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Route label for a request path. Path parameters are dropped, and paths
+/// not served by the node are grouped together so that they cannot inflate
+/// the number of series
+///
+/// ### Arguments
+///
+/// * `path` - Request path
+pub fn route_label(path: &str) -> &'static str {
+    let mut segments = path.trim_start_matches('/').split('/');
+    let route = match segments.next() {
+        Some("admin") => format!("admin/{}", segments.next().unwrap_or_default()),
+        Some(first) => first.to_string(),
+        None => String::new(),
+    };
+
+    METRICS_ROUTES
+        .iter()
+        .find(|r| **r == route)
+        .copied()
+        .unwrap_or("other")
+}
+
+/// Records a served request. Used with `warp::log::custom`
+///
+/// ### Arguments
+///
+/// * `info` - The request and its response
+pub fn record_request(info: warp::log::Info) {
+    let route = route_label(info.path());
+    let status = info.status().as_u16().to_string();
+    let metrics = metrics();
+
+    metrics.requests.with_label_values(&[route, &status]).inc();
+    metrics
+        .request_duration
+        .with_label_values(&[route, &status])
+        .observe(info.elapsed().as_secs_f64());
+}
+
+/// Records the outcome of a `get_data` cache lookup
+///
+/// ### Arguments
+///
+/// * `outcome` - Whether the lookup hit, missed or failed
+pub fn record_cache_lookup(outcome: CacheOutcome) {
+    metrics()
+        .cache_lookups
+        .with_label_values(&[&outcome.to_string()])
+        .inc();
+}
+
+/// Records a `get_data` request falling back to the DB
+pub fn record_db_fallback() {
+    metrics().db_fallbacks.inc();
+}
+
+/// Records a lookup passed by the membership filter for an address holding no data
+pub fn record_filter_false_lookup() {
+    metrics().filter_false_lookups.inc();
+}
+
+/// Records the time taken to save the membership filter
+///
+/// ### Arguments
+///
+/// * `duration` - Time taken by the save
+pub fn record_filter_save(duration: Duration) {
+    metrics()
+        .filter_save_duration
+        .observe(duration.as_secs_f64());
+}
+
+/// Records an error returned by a DB or cache
+///
+/// ### Arguments
+///
+/// * `backend` - Name of the store implementation
+/// * `operation` - Operation which failed
+pub fn record_backend_error(backend: &str, operation: &str) {
+    metrics()
+        .backend_errors
+        .with_label_values(&[backend, operation])
+        .inc();
+}
+
+/// Records whether the cache is unavailable
+///
+/// ### Arguments
+///
+/// * `degraded` - Whether requests are being served without the cache
+pub fn record_cache_degraded(degraded: bool) {
+    metrics().cache_degraded.set(degraded as i64);
+}
+
+/// Records what a sweep removed and the time it took
+///
+/// ### Arguments
+///
+/// * `report` - What the sweep removed
+/// * `duration` - Time taken by the sweep
+pub fn record_sweep(report: &SweepReport, duration: Duration) {
+    let metrics = metrics();
+    for (kind, count) in [
+        ("expired", report.expired),
+        ("empty", report.empty),
+        ("retention", report.aged_out),
+        ("filter", report.stale_filter_entries),
+    ] {
+        metrics
+            .sweep_removals
+            .with_label_values(&[kind])
+            .inc_by(count as u64);
+    }
+    metrics.sweep_duration.observe(duration.as_secs_f64());
+}
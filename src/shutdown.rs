@@ -0,0 +1,97 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// Signal shared by the servers and background tasks which need to stop
+/// when the node shuts down
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+    receiver: watch::Receiver<bool>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    /// Constructs a signal which has not been triggered
+    pub fn new() -> Self {
+        let (sender, receiver) = watch::channel(false);
+        Shutdown {
+            sender: Arc::new(sender),
+            receiver,
+        }
+    }
+
+    /// Starts shutting down everything waiting on the signal
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    /// Whether shutdown has started
+    pub fn is_triggered(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Waits until shutdown starts
+    pub async fn wait(&self) {
+        let mut receiver = self.receiver.clone();
+        // The sender lives as long as `self`, so this cannot fail
+        let _ = receiver.wait_for(|triggered| *triggered).await;
+    }
+}
+
+/// Waits for SIGINT or, on Unix, SIGTERM
+pub async fn wait_for_signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Failed to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => info!("SIGINT received"),
+        _ = terminate => info!("SIGTERM received"),
+    }
+}
+
+/// Runs a task until shutdown starts, then gives it up to `timeout` to finish
+/// what it is doing. Returns whether the task finished
+///
+/// ### Arguments
+///
+/// * `task` - Task which stops of its own accord once shutdown starts
+/// * `shutdown` - The node's shutdown signal
+/// * `timeout` - Time the task has to finish after shutdown starts
+pub async fn run_until_drained<F: Future>(task: F, shutdown: &Shutdown, timeout: Duration) -> bool {
+    tokio::pin!(task);
+    tokio::select! {
+        _ = &mut task => return true,
+        _ = shutdown.wait() => {}
+    }
+
+    tokio::time::timeout(timeout, task).await.is_ok()
+}
@@ -0,0 +1,220 @@
+use crate::constants::ALLOWLIST_KEY_PREFIX;
+use crate::db::handler::KvStoreConnection;
+use crate::db::schema::upgrade_record;
+use crate::filter::sharded::ShardedFilter;
+use crate::interfaces::SetSaveData;
+use crate::metrics::record_sweep;
+use crate::utils::{is_internal_key, rebuild_filter_in_place, save_filter_to_disk};
+use chrono::Utc;
+use futures::lock::Mutex;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info, instrument, warn};
+
+/// What a sweep removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SweepReport {
+    /// Keys removed because their expiry had passed
+    pub expired: usize,
+    /// Keys removed because they held no entries
+    pub empty: usize,
+    /// Entries removed because they were older than the retention period
+    pub aged_out: usize,
+    /// Filter entries removed because their address no longer holds data
+    pub stale_filter_entries: usize,
+}
+
+/// Whether the sweeper looks after a key: addresses and their allowlists.
+/// Other keys hold node state which is managed where it is used
+///
+/// ### Arguments
+///
+/// * `key` - Key to check
+fn is_swept_key(key: &str) -> bool {
+    !is_internal_key(key) || key.starts_with(ALLOWLIST_KEY_PREFIX)
+}
+
+/// Removes expired keys, keys left without entries and, if a retention period
+/// is set, address entries older than it. Addresses removed or trimmed are
+/// dropped from the cache, and removed addresses from the membership filter.
+/// Filters which support deletion are rebuilt if they still hold addresses
+/// which have no data, e.g. because they expired in the DB. Filters which do
+/// not are rebuilt only once a sweep removes addresses, as they always hold
+/// more items than there are live addresses.
+///
+/// Each key is swept under the DB lock, so concurrent writes are not lost.
+/// Addresses first written while a rebuild runs may be missing from the
+/// filter until the next write to them or the next rebuild.
+///
+/// ### Arguments
+///
+/// * `db` - The database connection
+/// * `cache` - The cache connection
+/// * `filter` - The membership filter
+/// * `retention` - Seconds entries are kept after they are saved, or forever if absent
+#[instrument(level = "debug", skip_all)]
+pub async fn sweep<D: KvStoreConnection, C: KvStoreConnection>(
+    db: Arc<Mutex<D>>,
+    cache: Arc<Mutex<C>>,
+    filter: &ShardedFilter,
+    retention: Option<u64>,
+) -> Result<SweepReport, String> {
+    let started = Instant::now();
+    let mut report = SweepReport::default();
+    let mut live_addresses = 0;
+    let mut removed_addresses = 0;
+    let deletes = filter.kind().supports_deletion();
+    let mut errors = Vec::new();
+
+    let keys = db
+        .lock()
+        .await
+        .get_keys()
+        .await
+        .map_err(|e| format!("Failed to list keys in DB with error: {}", e))?;
+    let now = Utc::now().timestamp().max(0) as u64;
+
+    for key in keys.iter().filter(|k| is_swept_key(k)) {
+        let is_address = !is_internal_key(key);
+        let mut db_lock = db.lock().await;
+
+        let outcome = match sweep_key(&mut *db_lock, key, is_address, now, retention).await {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                errors.push(format!("{}: {}", key, e));
+                continue;
+            }
+        };
+        report.aged_out += outcome.aged_out;
+
+        match outcome.removed {
+            Some(Removal::Expired) => report.expired += 1,
+            Some(Removal::Empty) => report.empty += 1,
+            None if is_address => live_addresses += 1,
+            None => {}
+        }
+
+        if outcome.removed.is_some() && is_address {
+            removed_addresses += 1;
+            // Deleted under the DB lock, so a write to the address after the removal re-adds it
+            if deletes && filter.delete(key).await {
+                report.stale_filter_entries += 1;
+            }
+        }
+        drop(db_lock);
+
+        // Trimmed addresses are dropped too, so that aged-out entries are not served from the cache
+        if outcome.removed.is_none() && outcome.aged_out == 0 {
+            continue;
+        }
+
+        if let Err(e) = cache.lock().await.del_data(key, None).await {
+            warn!("Failed to drop cached copy of {}: {}", key, e);
+        }
+    }
+
+    let filter_len = filter.len().await;
+    let stale = if deletes {
+        filter_len > live_addresses
+    } else {
+        removed_addresses > 0
+    };
+    if stale {
+        rebuild_filter_in_place(filter, db.clone()).await?;
+        report.stale_filter_entries += filter_len.saturating_sub(filter.len().await);
+    } else if report.stale_filter_entries > 0 {
+        save_filter_to_disk(filter, db).await?;
+    }
+
+    record_sweep(&report, started.elapsed());
+
+    if !errors.is_empty() {
+        return Err(format!(
+            "Failed to sweep {} keys with error: {}",
+            errors.len(),
+            errors.join(", ")
+        ));
+    }
+
+    info!(
+        "Sweep removed {} expired and {} empty keys, {} entries past retention and {} stale filter entries",
+        report.expired, report.empty, report.aged_out, report.stale_filter_entries
+    );
+    Ok(report)
+}
+
+/// Why a key was removed
+enum Removal {
+    Expired,
+    Empty,
+}
+
+/// What sweeping a single key did
+struct KeyOutcome {
+    removed: Option<Removal>,
+    aged_out: usize,
+}
+
+/// Sweeps a single key, which the caller holds the DB lock for
+///
+/// ### Arguments
+///
+/// * `db` - The database connection
+/// * `key` - Key to sweep
+/// * `is_address` - Whether the key holds an address's entries
+/// * `now` - Unix time in seconds of the sweep
+/// * `retention` - Seconds entries are kept after they are saved, or forever if absent
+async fn sweep_key<D: KvStoreConnection>(
+    db: &mut D,
+    key: &str,
+    is_address: bool,
+    now: u64,
+    retention: Option<u64>,
+) -> Result<KeyOutcome, Box<dyn std::error::Error + Send + Sync>> {
+    let mut outcome = KeyOutcome {
+        removed: None,
+        aged_out: 0,
+    };
+
+    if db
+        .get_expiry(key)
+        .await?
+        .is_some_and(|expiry| expiry <= now)
+    {
+        db.del_data(key, None).await?;
+        outcome.removed = Some(Removal::Expired);
+        return Ok(outcome);
+    }
+
+    // Stores may report a key left without entries as holding no data
+    let mut entries = db.get_data::<Value>(key, None).await?.unwrap_or_default();
+
+    if let (true, Some(retention)) = (is_address, retention) {
+        let mut aged_out = Vec::new();
+        for (value_id, entry) in entries.iter_mut() {
+            // Entries dated by this upgrade are saved, so that retention counts from now on
+            if upgrade_record::<SetSaveData>(entry)? {
+                db.set_data(key, value_id, entry.clone()).await?;
+            }
+
+            let saved_at = entry.get("saved_at").and_then(Value::as_u64).unwrap_or(now);
+            if saved_at.saturating_add(retention) <= now {
+                aged_out.push(value_id.clone());
+            }
+        }
+
+        for value_id in aged_out {
+            db.del_data(key, Some(&value_id)).await?;
+            entries.remove(&value_id);
+            outcome.aged_out += 1;
+        }
+    }
+
+    if entries.is_empty() {
+        db.del_data(key, None).await?;
+        outcome.removed = Some(Removal::Empty);
+    }
+
+    Ok(outcome)
+}
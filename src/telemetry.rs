@@ -0,0 +1,142 @@
+use crate::constants::TRACER_NAME;
+use crate::interfaces::{LogConfig, TelemetryConfig};
+use crate::logging::{construct_log_layer, request_id};
+use crate::metrics::route_label;
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::TracerProvider;
+use opentelemetry_sdk::{runtime, Resource};
+use tracing::{error, info_span, Span};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+use warp::hyper::HeaderMap;
+
+// ========== SUBSCRIBER ========== //
+
+/// Keeps the log writer and span exporter of the process running
+pub struct TracingGuard {
+    provider: Option<TracerProvider>,
+    _log_writer: Option<WorkerGuard>,
+}
+
+impl TracingGuard {
+    /// Flushes the spans and log lines not yet written
+    pub async fn shutdown(self) {
+        // Exporting blocks until the last spans are sent, so keep it off the async workers
+        if let Some(provider) = self.provider {
+            match tokio::task::spawn_blocking(move || provider.shutdown()).await {
+                Ok(Err(e)) => error!("Failed to flush traces: {}", e),
+                Err(e) => error!("Failed to flush traces: {}", e),
+                Ok(Ok(())) => {}
+            }
+        }
+    }
+}
+
+/// Installs the tracing subscriber for the process, logging as configured and,
+/// if an OTLP endpoint is configured, exporting spans to it. Returns the guard
+/// which must be shut down on exit to flush what is still buffered
+///
+/// ### Arguments
+///
+/// * `logging` - Logging settings from config
+/// * `config` - Telemetry settings from config
+pub fn init_tracing(logging: &LogConfig, config: &TelemetryConfig) -> Result<TracingGuard, String> {
+    let (log_layer, log_writer) = construct_log_layer(logging)?;
+
+    let provider = match &config.otlp_endpoint {
+        Some(endpoint) => Some(construct_tracer_provider(endpoint, &config.service_name)?),
+        None => None,
+    };
+    let otel_layer = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer(TRACER_NAME))
+            .with_filter(LevelFilter::DEBUG)
+    });
+
+    tracing_subscriber::registry()
+        .with(log_layer)
+        .with(otel_layer)
+        .try_init()
+        .map_err(|e| format!("Failed to install tracing subscriber with error: {}", e))?;
+
+    Ok(TracingGuard {
+        provider,
+        _log_writer: log_writer,
+    })
+}
+
+/// Constructs a provider exporting spans in batches to an OTLP collector over gRPC
+///
+/// ### Arguments
+///
+/// * `endpoint` - URL of the collector, e.g. `http://localhost:4317`
+/// * `service_name` - Name the node's spans are reported under
+fn construct_tracer_provider(endpoint: &str, service_name: &str) -> Result<TracerProvider, String> {
+    let exporter = SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|e| format!("Failed to construct OTLP exporter with error: {}", e))?;
+
+    Ok(TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            service_name.to_string(),
+        )]))
+        .build())
+}
+
+// ========== PROPAGATION ========== //
+
+/// Reads request headers for trace context propagation
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// Reads the W3C trace context sent by a caller in the `traceparent` and
+/// `tracestate` headers, which is empty if the caller sent none
+///
+/// ### Arguments
+///
+/// * `headers` - Request headers
+pub fn extract_trace_context(headers: &HeaderMap) -> Context {
+    TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+}
+
+/// Span covering a request, which the handler and storage spans of the request
+/// are nested in. Its request ID and route are on every line logged while serving
+/// the request. Continues the caller's trace if it sent one. Used with `warp::trace`
+///
+/// ### Arguments
+///
+/// * `info` - The request
+pub fn request_span(info: warp::trace::Info) -> Span {
+    let route = route_label(info.path());
+    let span = info_span!(
+        "request",
+        request_id = %request_id(info.request_headers()),
+        method = %info.method(),
+        route = %route,
+        otel.name = %format!("{} {}", info.method(), route),
+        otel.kind = "server",
+    );
+    span.set_parent(extract_trace_context(info.request_headers()));
+    span
+}
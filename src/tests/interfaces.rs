@@ -1,6 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use crate::db::handler::{CacheHandler, KvStoreConnection};
+use crate::interfaces::{RateLimit, StoreStats};
 use async_trait::async_trait;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use valence_core::utils::serialize_data;
@@ -19,6 +22,16 @@ pub struct GetReturn {
 #[derive(Clone)]
 pub struct DbStub {
     data: Option<String>,
+    claimed: HashSet<String>,
+    tokens_taken: HashMap<String, u64>,
+    reachable: bool,
+}
+
+impl DbStub {
+    /// Makes the stub fail pings, as a store which cannot be reached would
+    pub fn disconnect(&mut self) {
+        self.reachable = false;
+    }
 }
 
 #[async_trait]
@@ -30,12 +43,42 @@ impl CacheHandler for DbStub {
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         Ok(())
     }
+
+    async fn claim_key(
+        &mut self,
+        key: &str,
+        _seconds: usize,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.claimed.insert(key.to_string()))
+    }
+
+    /// Buckets never refill, so waits are for a single token's refill time
+    async fn take_token(
+        &mut self,
+        key: &str,
+        limit: RateLimit,
+    ) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let taken = self.tokens_taken.entry(key.to_string()).or_default();
+        if *taken < limit.capacity {
+            *taken += 1;
+            return Ok(0);
+        }
+
+        Ok((1000.0 / limit.refill_per_second).ceil() as u64)
+    }
 }
 
 #[async_trait]
 impl KvStoreConnection for DbStub {
+    const BACKEND: &'static str = "stub";
+
     async fn init(_url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        Ok(DbStub { data: None })
+        Ok(DbStub {
+            data: None,
+            claimed: HashSet::new(),
+            tokens_taken: HashMap::new(),
+            reachable: true,
+        })
     }
 
     async fn get_data<T: DeserializeOwned>(
@@ -86,6 +129,193 @@ impl KvStoreConnection for DbStub {
 
         Ok(())
     }
+
+    async fn get_expiry(
+        &mut self,
+        _key: &str,
+    ) -> Result<Option<u64>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(None)
+    }
+
+    async fn get_keys(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Vec::new())
+    }
+
+    async fn get_stats(&mut self) -> Result<StoreStats, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(match &self.data {
+            Some(data) => StoreStats {
+                addresses: 1,
+                entries: 1,
+                bytes: data.len() as u64,
+            },
+            None => StoreStats::default(),
+        })
+    }
+
+    async fn ping(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if !self.reachable {
+            return Err("Store unreachable".into());
+        }
+        Ok(())
+    }
+}
+
+/// A stub store holding any number of keys in memory
+#[derive(Clone, Default)]
+pub struct MemoryStub {
+    pub data: HashMap<String, HashMap<String, serde_json::Value>>,
+    pub expiries: HashMap<String, u64>,
+    pub claimed: HashSet<String>,
+    /// Shared with clones of the stub, so that a test can take it down after handing it over
+    pub down: Arc<AtomicBool>,
+}
+
+impl MemoryStub {
+    /// Fails as a store which cannot be reached would, while the stub is down
+    fn reach(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.down.load(Ordering::SeqCst) {
+            return Err("Store unreachable".into());
+        }
+        Ok(())
+    }
+}
+
+/// Claims never lapse and rate limits always succeed
+#[async_trait]
+impl CacheHandler for MemoryStub {
+    async fn expire_entry(
+        &mut self,
+        _key: &str,
+        _seconds: usize,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.reach()
+    }
+
+    async fn claim_key(
+        &mut self,
+        key: &str,
+        _seconds: usize,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        self.reach()?;
+        Ok(self.claimed.insert(key.to_string()))
+    }
+
+    async fn take_token(
+        &mut self,
+        _key: &str,
+        _limit: RateLimit,
+    ) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        self.reach()?;
+        Ok(0)
+    }
+}
+
+#[async_trait]
+impl KvStoreConnection for MemoryStub {
+    const BACKEND: &'static str = "stub";
+
+    async fn init(_url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(MemoryStub::default())
+    }
+
+    async fn set_data<T: Serialize + Send>(
+        &mut self,
+        key: &str,
+        value_id: &str,
+        value: T,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.reach()?;
+        self.data
+            .entry(key.to_string())
+            .or_default()
+            .insert(value_id.to_string(), serde_json::to_value(value)?);
+        Ok(())
+    }
+
+    /// Expiries are recorded as Unix times, but never enforced
+    async fn set_data_with_expiry<T: Serialize + Send>(
+        &mut self,
+        key: &str,
+        value_id: &str,
+        value: T,
+        seconds: usize,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.reach()?;
+        self.data
+            .entry(key.to_string())
+            .or_default()
+            .insert(value_id.to_string(), serde_json::to_value(value)?);
+        self.expiries.insert(
+            key.to_string(),
+            chrono::Utc::now().timestamp() as u64 + seconds as u64,
+        );
+        Ok(())
+    }
+
+    async fn del_data(
+        &mut self,
+        key: &str,
+        value_id: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.reach()?;
+        match value_id {
+            Some(value_id) => {
+                if let Some(entries) = self.data.get_mut(key) {
+                    entries.remove(value_id);
+                }
+            }
+            None => {
+                self.data.remove(key);
+                self.expiries.remove(key);
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_data<T: DeserializeOwned>(
+        &mut self,
+        key: &str,
+        value_id: Option<&str>,
+    ) -> Result<Option<HashMap<String, T>>, Box<dyn std::error::Error + Send + Sync>> {
+        self.reach()?;
+        let Some(entries) = self.data.get(key) else {
+            return Ok(None);
+        };
+
+        let mut result = HashMap::new();
+        for (id, value) in entries {
+            if value_id.is_none_or(|value_id| value_id == id) {
+                result.insert(id.clone(), serde_json::from_value(value.clone())?);
+            }
+        }
+        Ok((!result.is_empty()).then_some(result))
+    }
+
+    async fn get_expiry(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<u64>, Box<dyn std::error::Error + Send + Sync>> {
+        self.reach()?;
+        Ok(self.expiries.get(key).copied())
+    }
+
+    async fn get_keys(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        self.reach()?;
+        Ok(self.data.keys().cloned().collect())
+    }
+
+    async fn get_stats(&mut self) -> Result<StoreStats, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(StoreStats::default())
+    }
+
+    async fn ping(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.reach()
+    }
+
+    /// Only errors from the stub being down count, not those from decoding values
+    fn is_unreachable(error: &(dyn std::error::Error + 'static)) -> bool {
+        error.to_string() == "Store unreachable"
+    }
 }
 
 fn get_de_data<T: DeserializeOwned>(v: String) -> HashMap<String, T> {
@@ -97,7 +327,7 @@ fn get_de_data<T: DeserializeOwned>(v: String) -> HashMap<String, T> {
         }
     };
     let de_map: HashMap<String, T> =
-        serde_json::from_str(&value.as_str().unwrap()).unwrap_or(HashMap::new());
+        serde_json::from_str(value.as_str().unwrap()).unwrap_or(HashMap::new());
 
     de_map
 }
@@ -0,0 +1,341 @@
+use crate::constants::TLS_HANDSHAKE_TIMEOUT;
+use crate::interfaces::TlsConfig;
+use crate::shutdown::{run_until_drained, Shutdown};
+use std::convert::Infallible;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::server::{
+    AllowAnyAuthenticatedClient, ClientHello, NoClientAuth, ResolvesServerCert,
+};
+use tokio_rustls::rustls::sign::{any_supported_type, CertifiedKey};
+use tokio_rustls::rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tracing::{error, info, warn};
+use warp::hyper::server::conn::Http;
+use warp::hyper::service::service_fn;
+use warp::{Filter, Reply};
+
+/// Address of the client a request was received from, set on requests served
+/// over TLS where warp cannot see the underlying connection
+#[derive(Debug, Clone, Copy)]
+pub struct ClientAddr(pub SocketAddr);
+
+// ========== CERTIFICATES ========== //
+
+/// Serves the most recently loaded certificate and key, which can be replaced
+/// while the server is running
+pub struct ReloadingCertResolver {
+    cert_path: String,
+    key_path: String,
+    current: RwLock<Arc<CertifiedKey>>,
+    modified: RwLock<Option<SystemTime>>,
+}
+
+impl ReloadingCertResolver {
+    /// Loads the certificate chain and key from disk
+    ///
+    /// ### Arguments
+    ///
+    /// * `cert_path` - Path of the PEM certificate chain
+    /// * `key_path` - Path of the PEM private key
+    pub fn new(cert_path: &str, key_path: &str) -> Result<Self, String> {
+        Ok(ReloadingCertResolver {
+            cert_path: cert_path.to_string(),
+            key_path: key_path.to_string(),
+            current: RwLock::new(Arc::new(load_certified_key(cert_path, key_path)?)),
+            modified: RwLock::new(last_modified(&[cert_path, key_path])),
+        })
+    }
+
+    /// The certificate chain and key currently served
+    pub fn current(&self) -> Arc<CertifiedKey> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Reloads the certificate and key if either file has changed since they
+    /// were last loaded, returning whether they were replaced. The current
+    /// certificate is kept if the new files cannot be loaded
+    pub fn reload_if_changed(&self) -> Result<bool, String> {
+        let modified = last_modified(&[&self.cert_path, &self.key_path]);
+        if modified == *self.modified.read().unwrap() {
+            return Ok(false);
+        }
+
+        let certified_key = load_certified_key(&self.cert_path, &self.key_path)?;
+        *self.current.write().unwrap() = Arc::new(certified_key);
+        *self.modified.write().unwrap() = modified;
+        Ok(true)
+    }
+}
+
+impl ResolvesServerCert for ReloadingCertResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+/// Latest modification time of a set of files
+///
+/// ### Arguments
+///
+/// * `paths` - Paths of the files
+fn last_modified(paths: &[&str]) -> Option<SystemTime> {
+    paths
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .max()
+}
+
+/// Reads the certificates in a PEM file
+///
+/// ### Arguments
+///
+/// * `path` - Path of the PEM file
+fn load_certs(path: &str) -> Result<Vec<Certificate>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {path} with error: {e}"))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .map_err(|e| format!("Failed to read certificates from {path} with error: {e}"))?;
+
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {path}"));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+/// Reads the first private key in a PEM file
+///
+/// ### Arguments
+///
+/// * `path` - Path of the PEM file
+fn load_private_key(path: &str) -> Result<PrivateKey, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {path} with error: {e}"))?;
+    let items = rustls_pemfile::read_all(&mut BufReader::new(file))
+        .map_err(|e| format!("Failed to read private key from {path} with error: {e}"))?;
+
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or(format!("No private key found in {path}"))
+}
+
+/// Loads a certificate chain and its private key
+///
+/// ### Arguments
+///
+/// * `cert_path` - Path of the PEM certificate chain
+/// * `key_path` - Path of the PEM private key
+fn load_certified_key(cert_path: &str, key_path: &str) -> Result<CertifiedKey, String> {
+    let certs = load_certs(cert_path)?;
+    let key = any_supported_type(&load_private_key(key_path)?)
+        .map_err(|e| format!("Unsupported private key in {key_path}: {e}"))?;
+
+    Ok(CertifiedKey::new(certs, key))
+}
+
+/// Builds the TLS settings for the listener. Client certificates are required
+/// and verified against the configured CA, if any
+///
+/// ### Arguments
+///
+/// * `config` - TLS settings from config
+/// * `resolver` - Source of the server certificate
+pub fn build_server_config(
+    config: &TlsConfig,
+    resolver: Arc<ReloadingCertResolver>,
+) -> Result<ServerConfig, String> {
+    let client_verifier = match &config.client_ca_path {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(path)? {
+                roots
+                    .add(&cert)
+                    .map_err(|e| format!("Invalid client CA in {path}: {e}"))?;
+            }
+            AllowAnyAuthenticatedClient::new(roots).boxed()
+        }
+        None => NoClientAuth::boxed(),
+    };
+
+    let mut server_config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(client_verifier)
+        .with_cert_resolver(resolver);
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(server_config)
+}
+
+// ========== SERVER ========== //
+
+/// Serves routes on all interfaces, over TLS if it is configured. Once shutdown
+/// starts, no new connections are accepted and requests in flight are given up
+/// to `drain_timeout` to complete before this returns
+///
+/// ### Arguments
+///
+/// * `routes` - Routes to serve
+/// * `port` - Port to listen on
+/// * `tls` - TLS settings from config, if any
+/// * `shutdown` - The node's shutdown signal
+/// * `drain_timeout` - Time requests in flight have to complete once shutdown starts
+pub async fn serve<F, R>(
+    routes: F,
+    port: u16,
+    tls: Option<TlsConfig>,
+    shutdown: Shutdown,
+    drain_timeout: Duration,
+) where
+    F: Filter<Extract = (R,), Error = Infallible> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let drained = match tls {
+        Some(tls) => match serve_tls(routes, addr, tls, shutdown, drain_timeout).await {
+            Ok(drained) => drained,
+            Err(e) => panic!("Failed to serve over TLS with error: {}", e),
+        },
+        None => {
+            let signal = shutdown.clone();
+            let (_, server) = warp::serve(routes)
+                .bind_with_graceful_shutdown(addr, async move { signal.wait().await });
+            run_until_drained(server, &shutdown, drain_timeout).await
+        }
+    };
+
+    if drained {
+        info!("Server on port {} drained", port);
+    } else {
+        warn!(
+            "Server on port {} still had requests in flight after {}s",
+            port,
+            drain_timeout.as_secs()
+        );
+    }
+}
+
+/// Serves routes over TLS, checking the certificate files for changes every
+/// `reload_interval` seconds. Returns whether all connections were drained
+/// within `drain_timeout` once shutdown started
+///
+/// ### Arguments
+///
+/// * `routes` - Routes to serve
+/// * `addr` - Address to listen on
+/// * `config` - TLS settings from config
+/// * `shutdown` - The node's shutdown signal
+/// * `drain_timeout` - Time requests in flight have to complete once shutdown starts
+pub async fn serve_tls<F, R>(
+    routes: F,
+    addr: SocketAddr,
+    config: TlsConfig,
+    shutdown: Shutdown,
+    drain_timeout: Duration,
+) -> Result<bool, String>
+where
+    F: Filter<Extract = (R,), Error = Infallible> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    let resolver = Arc::new(ReloadingCertResolver::new(
+        &config.cert_path,
+        &config.key_path,
+    )?);
+    let acceptor = TlsAcceptor::from(Arc::new(build_server_config(&config, resolver.clone())?));
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| format!("Failed to bind to {addr} with error: {e}"))?;
+
+    let reload_interval = Duration::from_secs(config.reload_interval.max(1));
+    let reload_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(reload_interval);
+        while !reload_shutdown.is_triggered() {
+            interval.tick().await;
+            match resolver.reload_if_changed() {
+                Ok(true) => info!("TLS certificate reloaded"),
+                Ok(false) => {}
+                Err(e) => error!("Failed to reload TLS certificate: {}", e),
+            }
+        }
+    });
+
+    info!(
+        "Serving TLS{}",
+        if config.client_ca_path.is_some() {
+            " with client certificate verification"
+        } else {
+            ""
+        }
+    );
+
+    // Every connection holds a sender, so the receiver closes once all have finished
+    let (connections, mut drained) = mpsc::channel::<()>(1);
+
+    let service = warp::service(routes);
+    loop {
+        let (stream, remote) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!("Failed to accept connection: {}", e);
+                    continue;
+                }
+            },
+            _ = shutdown.wait() => break,
+        };
+
+        let acceptor = acceptor.clone();
+        let service = service.clone();
+        let shutdown = shutdown.clone();
+        let connection = connections.clone();
+        tokio::spawn(async move {
+            let _connection = connection;
+
+            let stream = match tokio::time::timeout(
+                Duration::from_secs(TLS_HANDSHAKE_TIMEOUT),
+                acceptor.accept(stream),
+            )
+            .await
+            {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => return warn!("TLS handshake with {} failed: {}", remote, e),
+                Err(_) => return warn!("TLS handshake with {} timed out", remote),
+            };
+
+            let handler = service_fn(move |mut req| {
+                req.extensions_mut().insert(ClientAddr(remote));
+                let mut service = service.clone();
+                async move { warp::hyper::service::Service::call(&mut service, req).await }
+            });
+
+            // Finish the request in flight, if any, then close the connection
+            let conn = Http::new().serve_connection(stream, handler);
+            tokio::pin!(conn);
+            let result = tokio::select! {
+                result = conn.as_mut() => result,
+                _ = shutdown.wait() => {
+                    conn.as_mut().graceful_shutdown();
+                    conn.await
+                }
+            };
+
+            if let Err(e) = result {
+                warn!("Connection with {} failed: {}", remote, e);
+            }
+        });
+    }
+
+    drop(connections);
+    Ok(tokio::time::timeout(drain_timeout, drained.recv())
+        .await
+        .is_ok())
+}